@@ -0,0 +1,375 @@
+use crate::construct::Term;
+use crate::debruijn;
+use crate::define;
+use crate::types::Type;
+use std::collections::HashMap;
+
+const RECURSION_LIMIT: usize = 1000;
+
+/// Index of an interned node. Equal ids mean structurally equal terms.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TermId(usize);
+
+/// Index of an interned type annotation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TypeId(usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    Var(char, Option<usize>),
+    Lambda(char, Option<usize>, Option<TypeId>, TermId),
+    App(TermId, TermId),
+    Def(char, Option<usize>, TermId),
+}
+
+/// Hash-consed term store, so equal subterms are stored once.
+#[derive(Debug, Default)]
+pub struct Arena {
+    nodes: Vec<Node>,
+    table: HashMap<Node, TermId>,
+    types: Vec<Type>,
+    type_table: HashMap<Type, TypeId>,
+    next_id: usize,
+}
+
+impl Arena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node(&self, id: TermId) -> Node {
+        self.nodes[id.0]
+    }
+
+    pub fn intern(&mut self, node: Node) -> TermId {
+        if let Some(id) = self.table.get(&node) {
+            return *id;
+        }
+        if let Node::Var(_, Some(id)) | Node::Lambda(_, Some(id), ..) | Node::Def(_, Some(id), _) =
+            node
+        {
+            self.next_id = self.next_id.max(id + 1);
+        }
+        let id = TermId(self.nodes.len());
+        self.nodes.push(node);
+        self.table.insert(node, id);
+        id
+    }
+
+    pub fn intern_type(&mut self, ty: &Type) -> TypeId {
+        if let Some(id) = self.type_table.get(ty) {
+            return *id;
+        }
+        let id = TypeId(self.types.len());
+        self.types.push(ty.clone());
+        self.type_table.insert(ty.clone(), id);
+        id
+    }
+
+    pub fn ty(&self, id: TypeId) -> &Type {
+        &self.types[id.0]
+    }
+
+    pub fn intern_term(&mut self, term: &Term) -> TermId {
+        let node = match term {
+            Term::Var(name, id) => Node::Var(*name, *id),
+            Term::Lambda(name, id, ty, body) => {
+                let ty = ty.as_ref().map(|ty| self.intern_type(ty));
+                Node::Lambda(*name, *id, ty, self.intern_term(body))
+            }
+            Term::App(func, arg) => Node::App(self.intern_term(func), self.intern_term(arg)),
+            Term::Def(name, id, body) => Node::Def(*name, *id, self.intern_term(body)),
+            Term::Let(name, id, _, value, body) => {
+                let body = self.intern_term(body);
+                let func = self.intern(Node::Lambda(*name, *id, None, body));
+                Node::App(func, self.intern_term(value))
+            }
+        };
+        self.intern(node)
+    }
+
+    pub fn to_term(&self, id: TermId) -> Term {
+        match self.node(id) {
+            Node::Var(name, id) => Term::Var(name, id),
+            Node::Lambda(name, id, ty, body) => Term::Lambda(
+                name,
+                id,
+                ty.map(|ty| self.ty(ty).clone()),
+                Box::new(self.to_term(body)),
+            ),
+            Node::App(func, arg) => {
+                Term::App(Box::new(self.to_term(func)), Box::new(self.to_term(arg)))
+            }
+            Node::Def(name, id, body) => Term::Def(name, id, Box::new(self.to_term(body))),
+        }
+    }
+
+    /// Reduces `term` like `eval::eval`, returning the normal form and beta steps.
+    pub fn eval(&mut self, mut term: TermId) -> Result<(TermId, usize), String> {
+        let mut steps = 0;
+        let mut seen = HashMap::new();
+        loop {
+            // Copies get fresh binders, so a cycle only repeats a term up to
+            // alpha-equivalence.
+            if let Some(previous) = seen.insert(debruijn::debruijn(&self.to_term(term)), steps) {
+                return Err(format!("diverges: cycle of length {}", steps - previous));
+            }
+            let mut recursion = 0;
+            match self.step(term, &mut recursion)? {
+                Some(next) => {
+                    term = next;
+                    steps += 1;
+                }
+                None => return Ok((term, steps)),
+            }
+        }
+    }
+
+    fn step(&mut self, term: TermId, recursion: &mut usize) -> Result<Option<TermId>, String> {
+        bump_recursion_count(recursion)?;
+        match self.node(term) {
+            Node::App(func, arg) => match self.node(func) {
+                Node::Lambda(_, id, _, body) => {
+                    // Every copy of an argument with binders must be distinct,
+                    // so results are only shared when there are none.
+                    let mut memo = HashMap::new();
+                    let memo = (!self.binds(arg)).then_some(&mut memo);
+                    let result =
+                        self.substitute(define::id(id)?, body, arg, memo, &mut false, recursion)?;
+                    Ok(Some(result))
+                }
+                Node::App(..) | Node::Var(..) | Node::Def(..) => {
+                    if let Some(func) = self.step(func, recursion)? {
+                        return Ok(Some(self.intern(Node::App(func, arg))));
+                    }
+                    let arg = self.step(arg, recursion)?;
+                    Ok(arg.map(|arg| self.intern(Node::App(func, arg))))
+                }
+            },
+            Node::Lambda(name, id, ty, body) => {
+                let body = self.step(body, recursion)?;
+                Ok(body.map(|body| self.intern(Node::Lambda(name, id, ty, body))))
+            }
+            Node::Def(name, id, body) => {
+                let body = self.step(body, recursion)?;
+                Ok(body.map(|body| self.intern(Node::Def(name, id, body))))
+            }
+            Node::Var(..) => Ok(None),
+        }
+    }
+
+    fn substitute(
+        &mut self,
+        id: usize,
+        body: TermId,
+        arg: TermId,
+        mut memo: Option<&mut HashMap<TermId, TermId>>,
+        copied: &mut bool,
+        recursion: &mut usize,
+    ) -> Result<TermId, String> {
+        if let Some(result) = memo.as_ref().and_then(|memo| memo.get(&body)) {
+            return Ok(*result);
+        }
+        bump_recursion_count(recursion)?;
+        let result = match self.node(body) {
+            Node::Var(_, child_id) => {
+                if child_id != Some(id) {
+                    body
+                } else if *copied {
+                    self.freshen(arg, &mut Vec::new())
+                } else {
+                    *copied = true;
+                    arg
+                }
+            }
+            Node::Lambda(_, binder, _, _) | Node::Def(_, binder, _) if binder == Some(id) => body,
+            Node::Lambda(name, lambda_id, ty, lambda_body) => {
+                let lambda_body =
+                    self.substitute(id, lambda_body, arg, memo.as_deref_mut(), copied, recursion)?;
+                self.intern(Node::Lambda(name, lambda_id, ty, lambda_body))
+            }
+            Node::App(func, app_arg) => {
+                let func =
+                    self.substitute(id, func, arg, memo.as_deref_mut(), copied, recursion)?;
+                let app_arg =
+                    self.substitute(id, app_arg, arg, memo.as_deref_mut(), copied, recursion)?;
+                self.intern(Node::App(func, app_arg))
+            }
+            Node::Def(name, def_id, def_body) => {
+                let def_body =
+                    self.substitute(id, def_body, arg, memo.as_deref_mut(), copied, recursion)?;
+                self.intern(Node::Def(name, def_id, def_body))
+            }
+        };
+        if let Some(memo) = memo {
+            memo.insert(body, result);
+        }
+        Ok(result)
+    }
+
+    fn binds(&self, term: TermId) -> bool {
+        match self.node(term) {
+            Node::Var(..) => false,
+            Node::Lambda(..) | Node::Def(..) => true,
+            Node::App(func, arg) => self.binds(func) || self.binds(arg),
+        }
+    }
+
+    /// A copy of `term` whose binders have new ids.
+    fn freshen(&mut self, term: TermId, scope: &mut Vec<(usize, usize)>) -> TermId {
+        match self.node(term) {
+            Node::Var(name, id) => match scope.iter().rev().find(|(old, _)| Some(*old) == id) {
+                Some((_, fresh)) => self.intern(Node::Var(name, Some(*fresh))),
+                None => term,
+            },
+            Node::Lambda(name, id, ty, body) => {
+                let fresh = self.rebind(id, scope);
+                let body = self.freshen(body, scope);
+                if id.is_some() {
+                    scope.pop();
+                }
+                self.intern(Node::Lambda(name, fresh, ty, body))
+            }
+            Node::Def(name, id, body) => {
+                let fresh = self.rebind(id, scope);
+                let body = self.freshen(body, scope);
+                if id.is_some() {
+                    scope.pop();
+                }
+                self.intern(Node::Def(name, fresh, body))
+            }
+            Node::App(func, arg) => {
+                let func = self.freshen(func, scope);
+                let arg = self.freshen(arg, scope);
+                self.intern(Node::App(func, arg))
+            }
+        }
+    }
+
+    fn rebind(&mut self, id: Option<usize>, scope: &mut Vec<(usize, usize)>) -> Option<usize> {
+        let old = id?;
+        let fresh = self.next_id;
+        self.next_id += 1;
+        scope.push((old, fresh));
+        Some(fresh)
+    }
+}
+
+/// Reduces `term` through an arena, returning the number of beta steps taken.
+pub fn eval(term: &mut Term) -> Result<usize, String> {
+    let mut arena = Arena::new();
+    let id = arena.intern_term(term);
+    let (normal, steps) = arena.eval(id)?;
+    *term = arena.to_term(normal);
+    Ok(steps)
+}
+
+fn bump_recursion_count(recursion: &mut usize) -> Result<(), String> {
+    *recursion += 1;
+    if *recursion >= RECURSION_LIMIT {
+        Err(String::from("Hit recursion limit"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::construct::app;
+    use crate::construct::lambda;
+    use crate::construct::var;
+    use crate::eval;
+    use crate::pipeline;
+    use crate::pipeline::Pipeline;
+    use crate::pipeline::PipelineResult;
+
+    macro_rules! same {
+        ($text:literal) => {
+            if let Ok(PipelineResult::Term(term)) = pipeline::pipeline(Pipeline::Define, $text) {
+                let mut tree = term.clone();
+                let tree_steps = eval::eval(&mut tree).unwrap();
+                let mut shared = term;
+                let shared_steps = eval(&mut shared).unwrap();
+                assert!(
+                    debruijn::alpha_equivalent(&shared, &tree),
+                    "{} and {}",
+                    shared,
+                    tree
+                );
+                assert_eq!(shared_steps, tree_steps);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    #[test]
+    fn stuck_function() {
+        // A function that can't reduce any further doesn't stop its argument
+        // from reducing.
+        same!("((f x) ((y -> y) z))");
+        if let Ok(PipelineResult::Term(mut term)) =
+            pipeline::pipeline(Pipeline::Define, "((f x) ((y -> y) z))")
+        {
+            assert_eq!(eval(&mut term), Ok(1));
+            assert_eq!(format!("{}", term), "((f x) z)");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn intern() {
+        let mut arena = Arena::new();
+        let term = app(lambda('x', var('x')), lambda('x', var('x')));
+        let id = arena.intern_term(&term);
+        assert_eq!(arena.len(), 3);
+        assert_eq!(arena.to_term(id), term);
+        if let Node::App(func, arg) = arena.node(id) {
+            assert_eq!(func, arg);
+        } else {
+            panic!();
+        }
+        assert_eq!(arena.intern_term(&term), id);
+        assert_ne!(arena.intern_term(&lambda('y', var('y'))), id);
+        assert_eq!(arena.len(), 5);
+    }
+
+    #[test]
+    fn test() {
+        same!("x");
+        same!("((x -> x) y)");
+        same!("((x -> (y -> x)) z)");
+        same!("((a -> a) ((b -> c) d))");
+        same!("(f (g ((x -> x) x)))");
+        same!("((f -> (f x)) (y -> y))");
+        same!("((y -> (a -> a)) ((x -> (x x)) (x -> (x x))))");
+        same!("((x -> (x x)) ((y -> y) (z -> z)))");
+        same!("((n -> (f -> (x -> (f ((n f) x))))) (f -> (x -> (f (f (f x))))))");
+        same!("(((m -> (n -> (f -> (x -> ((m f) ((n f) x)))))) (f -> (x -> (f x)))) (f -> (x -> (f x))))");
+        same!("(((p -> (q -> ((p q) p))) (x -> (y -> x))) (x -> (y -> y)))");
+        same!("((p -> (p (x -> (y -> x)))) (p -> ((p a) b)))");
+        same!("(((x -> (x x)) (x -> (y -> (x y)))) z)");
+        same!("((s -> (s s)) (a -> (b -> (a b))))");
+    }
+
+    #[test]
+    fn divergence() {
+        if let Ok(PipelineResult::Term(mut term)) =
+            pipeline::pipeline(Pipeline::Define, "((x -> (x x)) (x -> (x x)))")
+        {
+            assert_eq!(
+                eval(&mut term),
+                Err(String::from("diverges: cycle of length 1"))
+            );
+        } else {
+            panic!();
+        }
+    }
+}
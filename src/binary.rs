@@ -0,0 +1,188 @@
+use crate::construct::Term;
+use crate::debruijn;
+use crate::debruijn::DeBruijn;
+use crate::parse;
+
+/// Encodes a closed term in binary lambda calculus.
+pub fn encode(term: &Term) -> Result<String, String> {
+    let mut bits = String::new();
+    do_encode(&debruijn::debruijn(term), &mut bits)?;
+    Ok(bits)
+}
+
+fn do_encode(term: &DeBruijn, bits: &mut String) -> Result<(), String> {
+    match term {
+        DeBruijn::Bound(index) => {
+            bits.extend(std::iter::repeat_n('1', index + 1));
+            bits.push('0');
+        }
+        DeBruijn::Free(name) => return Err(format!("Cannot encode free variable `{}`", name)),
+        DeBruijn::Lambda(body) => {
+            bits.push_str("00");
+            do_encode(body, bits)?;
+        }
+        DeBruijn::App(func, arg) => {
+            bits.push_str("01");
+            do_encode(func, bits)?;
+            do_encode(arg, bits)?;
+        }
+        DeBruijn::Let(value, body) => {
+            bits.push_str("0100");
+            do_encode(body, bits)?;
+            do_encode(value, bits)?;
+        }
+        DeBruijn::Def(_) => return Err(String::from("Cannot encode a definition")),
+    }
+    Ok(())
+}
+
+/// Decodes binary lambda calculus into a defined term.
+pub fn decode(bits: &str) -> Result<Term, String> {
+    let bits: Vec<char> = bits.chars().collect();
+    if let Some(ch) = bits.iter().find(|ch| **ch != '0' && **ch != '1') {
+        return Err(format!("'{}' is not a bit", ch));
+    }
+    let mut decoder = Decoder {
+        bits,
+        position: 0,
+        scope: Vec::new(),
+        next_id: 0,
+        depth: 0,
+    };
+    let term = decoder.decode()?;
+    if decoder.position < decoder.bits.len() {
+        return Err(format!(
+            "Unexpected bits after term at bit {}",
+            decoder.position
+        ));
+    }
+    Ok(term)
+}
+
+struct Decoder {
+    bits: Vec<char>,
+    position: usize,
+    scope: Vec<usize>,
+    next_id: usize,
+    depth: usize,
+}
+
+impl Decoder {
+    fn next(&mut self) -> Result<char, String> {
+        let bit = self.bits.get(self.position).copied();
+        self.position += 1;
+        bit.ok_or_else(|| String::from("Unexpected end of bits"))
+    }
+
+    fn decode(&mut self) -> Result<Term, String> {
+        if self.depth == parse::NESTING_LIMIT {
+            return Err(String::from("Too deeply nested"));
+        }
+        self.depth += 1;
+        let term = self.decode_term();
+        self.depth -= 1;
+        term
+    }
+
+    fn decode_term(&mut self) -> Result<Term, String> {
+        match (self.next()?, self.next()?) {
+            ('0', '0') => {
+                let name = name(self.scope.len());
+                let id = self.next_id;
+                self.next_id += 1;
+                self.scope.push(id);
+                let body = self.decode()?;
+                self.scope.pop();
+                Ok(Term::Lambda(name, Some(id), None, Box::new(body)))
+            }
+            ('0', _) => {
+                let func = self.decode()?;
+                let arg = self.decode()?;
+                Ok(Term::App(Box::new(func), Box::new(arg)))
+            }
+            (_, second) => {
+                let mut index = 0;
+                let mut bit = second;
+                while bit == '1' {
+                    index += 1;
+                    bit = self.next()?;
+                }
+                if index >= self.scope.len() {
+                    return Err(format!("Variable index {} is unbound", index));
+                }
+                let binder = self.scope.len() - 1 - index;
+                Ok(Term::Var(name(binder), Some(self.scope[binder])))
+            }
+        }
+    }
+}
+
+fn name(depth: usize) -> char {
+    (b'a' + (depth % 26) as u8) as char
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline::define;
+
+    macro_rules! encode {
+        ($text:literal, $expected:literal) => {
+            let term = define($text);
+            let bits = encode(&term).unwrap();
+            assert_eq!(bits, $expected);
+            assert!(debruijn::alpha_equivalent(&decode(&bits).unwrap(), &term));
+        };
+    }
+
+    #[test]
+    fn test() {
+        encode!("(x -> x)", "0010");
+        encode!("(x -> (y -> x))", "0000110");
+        encode!(
+            "(x -> (y -> (z -> ((x z) (y z)))))",
+            "00000001011110100111010"
+        );
+        encode!("(x -> (x x))", "00011010");
+        encode!("((x -> x) (y -> y))", "0100100010");
+        encode!("(x -> (x -> x))", "000010");
+        assert_eq!(
+            encode(&define("(x -> y)")),
+            Err(String::from("Cannot encode free variable `y`"))
+        );
+        assert_eq!(
+            encode(&define("(f : f)")),
+            Err(String::from("Cannot encode a definition"))
+        );
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(format!("{}", decode("0000110").unwrap()), "(a -> (b -> a))");
+        assert_eq!(decode("001"), Err(String::from("Unexpected end of bits")));
+        assert_eq!(decode("0010 "), Err(String::from("' ' is not a bit")));
+        assert_eq!(
+            decode("00100"),
+            Err(String::from("Unexpected bits after term at bit 4"))
+        );
+        assert_eq!(
+            decode("00110"),
+            Err(String::from("Variable index 1 is unbound"))
+        );
+        let nested = |depth| format!("{}10", "00".repeat(depth));
+        assert!(decode(&nested(parse::NESTING_LIMIT - 1)).is_ok());
+        assert_eq!(
+            decode(&nested(parse::NESTING_LIMIT)),
+            Err(String::from("Too deeply nested"))
+        );
+        assert_eq!(
+            decode(&"00".repeat(20000)),
+            Err(String::from("Too deeply nested"))
+        );
+        let mut term = decode("010001101000011010").unwrap();
+        assert_eq!(format!("{}", term), "((a -> (a a)) (a -> (a a)))");
+        term = decode("0100100010").unwrap();
+        crate::eval::eval(&mut term).unwrap();
+        assert_eq!(format!("{}", term), "(a -> a)");
+    }
+}
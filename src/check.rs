@@ -0,0 +1,119 @@
+use crate::construct;
+use crate::construct::Term;
+use crate::define;
+use crate::eval;
+use crate::lex;
+use crate::parse;
+use crate::parse::Tree;
+use crate::source;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub passed: usize,
+    /// Located messages for the assertions that failed.
+    pub failures: Vec<String>,
+}
+
+/// Checks every top-level `(assert a == b)` up to beta-eta equivalence.
+pub fn check(input: &str) -> Result<Option<Report>, String> {
+    let (trees, errors) = parse::parse_recovering(&lex::lex_spanned(input)?);
+    if !trees.iter().any(|(tree, _)| is_assertion(tree)) {
+        return Ok(None);
+    }
+    let mut passed = 0;
+    let mut failures = errors;
+    for (tree, spans) in &trees {
+        if !is_assertion(tree) {
+            continue;
+        }
+        match assertion(tree) {
+            Ok(()) => passed += 1,
+            Err(err) => failures.push((spans[0], err)),
+        }
+    }
+    failures.sort_by_key(|(span, _)| span.start);
+    Ok(Some(Report {
+        passed,
+        failures: failures
+            .into_iter()
+            .map(|(span, err)| source::locate(input, span, &err))
+            .collect(),
+    }))
+}
+
+fn is_assertion(tree: &Tree) -> bool {
+    matches!(tree, Tree::Branch(items) if items.first() == Some(&Tree::Assert))
+}
+
+fn assertion(tree: &Tree) -> Result<(), String> {
+    let (mut left, mut right) = match tree {
+        Tree::Branch(items) => match items.as_slice() {
+            [Tree::Assert, left, Tree::Equals, right] => (side(left)?, side(right)?),
+            _ => return Err(String::from("Expected an assertion like `(assert a == b)`")),
+        },
+        _ => return Err(String::from("Expected an assertion like `(assert a == b)`")),
+    };
+    if eval::equivalent(&left, &right)? {
+        return Ok(());
+    }
+    let options = eval::Options {
+        eta: true,
+        ..eval::Options::default()
+    };
+    eval::eval_with(&mut left, &options)?;
+    eval::eval_with(&mut right, &options)?;
+    Err(format!(
+        "Assertion failed: left is `{}`, right is `{}`",
+        left, right
+    ))
+}
+
+fn side(tree: &Tree) -> Result<Term, String> {
+    let mut term = construct::construct(tree)?;
+    define::define(&mut term);
+    Ok(term)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        assert_eq!(check("((x -> x) y)"), Ok(None));
+        assert_eq!(
+            check("(assert ((x -> x) y) == y)\n(assert (x -> (f x)) == f)"),
+            Ok(Some(Report {
+                passed: 2,
+                failures: vec![],
+            }))
+        );
+        assert_eq!(
+            check("(x -> x)\n(assert (x -> (y -> x)) == (a -> (b -> b)))"),
+            Ok(Some(Report {
+                passed: 0,
+                failures: vec![String::from(
+                    "2:1: Assertion failed: left is `(x -> (y -> x))`, right is `(a -> (b -> b))`"
+                )],
+            }))
+        );
+        assert_eq!(
+            check("(assert x)\n(assert (x -> y z) == x)\n(assert x == x"),
+            Ok(Some(Report {
+                passed: 1,
+                failures: vec![
+                    String::from("1:1: Expected an assertion like `(assert a == b)`"),
+                    String::from("2:1: Lambda body has too many terms"),
+                    String::from("3:1: Unclosed parenthesis"),
+                ],
+            }))
+        );
+        assert_eq!(
+            check("(assert ((x -> (x x)) (x -> (x x))) == x)"),
+            Ok(Some(Report {
+                passed: 0,
+                failures: vec![String::from("1:1: diverges: cycle of length 1")],
+            }))
+        );
+    }
+}
@@ -1,11 +1,16 @@
 use crate::parse::Tree;
+use crate::source::SourceError;
+use crate::source::Span;
+use crate::types;
+use crate::types::Type;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Term {
     Var(char, Option<usize>),
-    Lambda(char, Option<usize>, Box<Term>),
+    Lambda(char, Option<usize>, Option<Type>, Box<Term>),
     App(Box<Term>, Box<Term>),
     Def(char, Option<usize>, Box<Term>),
+    Let(char, Option<usize>, bool, Box<Term>, Box<Term>),
 }
 
 pub fn var(name: char) -> Term {
@@ -13,7 +18,11 @@ pub fn var(name: char) -> Term {
 }
 
 pub fn lambda(name: char, body: Term) -> Term {
-    Term::Lambda(name, None, Box::new(body))
+    Term::Lambda(name, None, None, Box::new(body))
+}
+
+pub fn typed_lambda(name: char, ty: Type, body: Term) -> Term {
+    Term::Lambda(name, None, Some(ty), Box::new(body))
 }
 
 pub fn def(name: char, term: Term) -> Term {
@@ -24,6 +33,27 @@ pub fn app(func: Term, arg: Term) -> Term {
     Term::App(Box::new(func), Box::new(arg))
 }
 
+pub fn let_in(name: char, value: Term, body: Term) -> Term {
+    Term::Let(name, None, false, Box::new(value), Box::new(body))
+}
+
+/// A `let rec`, whose value is already `(Y (name -> value))`.
+pub fn let_rec(name: char, value: Term, body: Term) -> Term {
+    Term::Let(name, None, true, Box::new(value), Box::new(body))
+}
+
+pub fn redex(name: char, id: Option<usize>, value: Term, body: Term) -> Term {
+    app(Term::Lambda(name, id, None, Box::new(body)), value)
+}
+
+pub fn size(term: &Term) -> usize {
+    match term {
+        Term::Var(..) => 1,
+        Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => 1 + size(body),
+        Term::App(func, arg) | Term::Let(_, _, _, func, arg) => 1 + size(func) + size(arg),
+    }
+}
+
 #[cfg(test)]
 pub fn var_id(name: char, id: usize) -> Term {
     Term::Var(name, Some(id))
@@ -31,20 +61,195 @@ pub fn var_id(name: char, id: usize) -> Term {
 
 #[cfg(test)]
 pub fn lambda_id(name: char, id: usize, body: Term) -> Term {
-    Term::Lambda(name, Some(id), Box::new(body))
+    Term::Lambda(name, Some(id), None, Box::new(body))
 }
 
 pub fn construct(tree: &Tree) -> Result<Term, String> {
+    Ok(construct_located(tree, &[])?.0)
+}
+
+/// Like `construct`, also mapping tree spans to term spans in preorder.
+pub fn construct_located(tree: &Tree, spans: &[Span]) -> Result<(Term, Vec<Span>), String> {
+    let mut locator = Locator {
+        tree: spans,
+        term: Vec::new(),
+    };
+    let term = construct_node(tree, 0, &mut locator)?;
+    Ok((term, locator.term))
+}
+
+/// A term built from part of the input, with its spans in preorder.
+pub type Fragment = (Term, Vec<Span>);
+
+/// Constructs the valid parts of `tree`, reporting the innermost failures.
+pub fn construct_recovering(tree: &Tree, spans: &[Span]) -> (Vec<Fragment>, Vec<SourceError>) {
+    let mut terms = Vec::new();
+    let mut errors = Vec::new();
+    recover(tree, spans, &mut terms, &mut errors);
+    (terms, errors)
+}
+
+/// Returns whether `tree` failed to construct.
+fn recover(
+    tree: &Tree,
+    spans: &[Span],
+    terms: &mut Vec<Fragment>,
+    errors: &mut Vec<SourceError>,
+) -> bool {
+    let err = match construct_located(tree, spans) {
+        Ok(term) => {
+            terms.push(term);
+            return false;
+        }
+        Err(err) => err,
+    };
+    let mut patched = Vec::new();
+    if let Tree::Branch(branch) = tree {
+        let mut index = 1;
+        for child in branch {
+            let failed = matches!(child, Tree::Branch(_))
+                && recover(child, spans.get(index..).unwrap_or_default(), terms, errors);
+            patched.push(if failed {
+                Tree::Name('x')
+            } else {
+                child.clone()
+            });
+            index += tree_size(child);
+        }
+    }
+    let err = match tree {
+        Tree::Branch(branch) if patched != *branch => construct(&Tree::Branch(patched)).err(),
+        _ => Some(err),
+    };
+    if let Some(err) = err {
+        errors.push((spans.first().copied().unwrap_or_default(), err));
+    }
+    true
+}
+
+struct Locator<'a> {
+    tree: &'a [Span],
+    term: Vec<Span>,
+}
+
+impl Locator<'_> {
+    fn mark(&mut self, index: usize) {
+        let span = self.tree.get(index).copied().unwrap_or_default();
+        self.term.push(span);
+    }
+
+    /// Spans a binder without a branch to the end of the enclosing one.
+    fn mark_binder(&mut self, name: usize, branch: usize) {
+        let start = self.tree.get(name).copied().unwrap_or_default().start;
+        let end = self.tree.get(branch).copied().unwrap_or_default().end;
+        self.term.push(Span { start, end });
+    }
+}
+
+fn tree_size(tree: &Tree) -> usize {
     match tree {
-        Tree::Name(ch) => Ok(var(*ch)),
-        Tree::Branch(branch) => Ok(construct_branch(branch)?),
-        Tree::Arrow | Tree::Colon => panic!(),
+        Tree::Branch(branch) | Tree::Bracket(branch) => {
+            1 + branch.iter().map(tree_size).sum::<usize>()
+        }
+        _ => 1,
     }
 }
 
-fn construct_branch(branch: &[Tree]) -> Result<Term, String> {
-    let mut branch = branch.iter();
-    let node = if let Some(node) = branch.next() {
+fn construct_node(tree: &Tree, index: usize, locator: &mut Locator) -> Result<Term, String> {
+    match tree {
+        Tree::Name(ch) => {
+            locator.mark(index);
+            Ok(var(*ch))
+        }
+        Tree::Branch(branch) => Ok(construct_branch(branch, index, locator)?),
+        Tree::Bracket(_) => Err(String::from("Unexpected type annotation")),
+        Tree::Assert => Err(String::from("Assertions are only allowed at the top level")),
+        Tree::Equals => Err(String::from("Unexpected '=='")),
+        Tree::Arrow => Err(String::from("Unexpected arrow")),
+        Tree::Colon => Err(String::from("Unexpected colon")),
+        Tree::Let | Tree::Rec | Tree::In | Tree::Where | Tree::Bind => Err(misplaced(tree)),
+    }
+}
+
+/// The error for a binding keyword outside a `let` or `where`.
+fn misplaced(tree: &Tree) -> String {
+    let keyword = match tree {
+        Tree::Let => "`let`",
+        Tree::Rec => "`rec`",
+        Tree::In => "`in`",
+        Tree::Where => "`where`",
+        Tree::Bind => "'='",
+        _ => "keyword",
+    };
+    format!("Unexpected {}", keyword)
+}
+
+/// The fixpoint combinator `(g -> ((x -> (g (x x))) (x -> (g (x x)))))`.
+pub fn fixpoint() -> Term {
+    let half = lambda('x', app(var('g'), app(var('x'), var('x'))));
+    lambda('g', app(half.clone(), half))
+}
+
+/// Builds a `let` or `where`, binding through `Y` with `rec`.
+fn construct_let(
+    binding: &[(usize, &Tree)],
+    body: Option<(usize, &Tree)>,
+    index: usize,
+    locator: &mut Locator,
+) -> Result<Term, String> {
+    let (rec, binding) = match binding {
+        [(_, Tree::Rec), rest @ ..] => (true, rest),
+        _ => (false, binding),
+    };
+    let (name_index, name, value, body) = match (binding, body) {
+        ([(name_index, Tree::Name(name)), (_, Tree::Bind), value, (_, Tree::In), body], None) => {
+            (*name_index, *name, *value, *body)
+        }
+        ([(name_index, Tree::Name(name)), (_, Tree::Bind), value], Some(body)) => {
+            (*name_index, *name, *value, body)
+        }
+        (_, None) => return Err(String::from("Expected `(let x = a in b)`")),
+        (_, Some(_)) => return Err(String::from("Expected `(b where x = a)`")),
+    };
+    locator.mark(index);
+    let value = if rec {
+        let fixpoint = fixpoint();
+        for _ in 0..size(&fixpoint) + 1 {
+            locator.mark(index);
+        }
+        locator.mark_binder(name_index, index);
+        app(
+            fixpoint,
+            lambda(name, construct_node(value.1, value.0, locator)?),
+        )
+    } else {
+        construct_node(value.1, value.0, locator)?
+    };
+    let body = construct_node(body.1, body.0, locator)?;
+    Ok(if rec {
+        let_rec(name, value, body)
+    } else {
+        let_in(name, value, body)
+    })
+}
+
+fn construct_branch(branch: &[Tree], index: usize, locator: &mut Locator) -> Result<Term, String> {
+    let mut children = Vec::new();
+    let mut child_index = index + 1;
+    for child in branch {
+        children.push((child_index, child));
+        child_index += tree_size(child);
+    }
+    match children.as_slice() {
+        [(_, Tree::Let), binding @ ..] => return construct_let(binding, None, index, locator),
+        [body, (_, Tree::Where), binding @ ..] => {
+            return construct_let(binding, Some(*body), index, locator)
+        }
+        _ => (),
+    }
+    let mut branch = children.into_iter();
+
+    let (node_index, node) = if let Some(node) = branch.next() {
         node
     } else {
         return Err(String::from("Empty parentheses is invalid"));
@@ -54,9 +259,19 @@ fn construct_branch(branch: &[Tree]) -> Result<Term, String> {
             let node2 = branch.next();
             match node2 {
                 None => Err(String::from("Expected more symbols after name")),
-                Some(Tree::Arrow) => {
-                    if let Some(tree) = branch.next() {
-                        let term = lambda(*ch, construct(tree)?);
+                Some((_, Tree::Assert)) => {
+                    Err(String::from("Assertions are only allowed at the top level"))
+                }
+                Some((_, Tree::Equals)) => Err(String::from("Unexpected '=='")),
+                Some((_, keyword @ Tree::Let))
+                | Some((_, keyword @ Tree::Rec))
+                | Some((_, keyword @ Tree::In))
+                | Some((_, keyword @ Tree::Where))
+                | Some((_, keyword @ Tree::Bind)) => Err(misplaced(keyword)),
+                Some((_, Tree::Arrow)) => {
+                    if let Some((tree_index, tree)) = branch.next() {
+                        locator.mark(index);
+                        let term = lambda(*ch, construct_node(tree, tree_index, locator)?);
                         if branch.next().is_some() {
                             Err(String::from("Lambda body has too many terms"))
                         } else {
@@ -66,9 +281,29 @@ fn construct_branch(branch: &[Tree]) -> Result<Term, String> {
                         Err(String::from("Expected lambda body after arrow"))
                     }
                 }
-                Some(Tree::Colon) => {
-                    if let Some(tree) = branch.next() {
-                        let term = def(*ch, construct(tree)?);
+                Some((_, Tree::Bracket(annotation))) => {
+                    let ty = construct_type(annotation)?;
+                    if let Some((_, Tree::Arrow)) = branch.next() {
+                    } else {
+                        return Err(String::from("Expected arrow after type annotation"));
+                    }
+                    if let Some((tree_index, tree)) = branch.next() {
+                        locator.mark(index);
+                        let term =
+                            typed_lambda(*ch, ty, construct_node(tree, tree_index, locator)?);
+                        if branch.next().is_some() {
+                            Err(String::from("Lambda body has too many terms"))
+                        } else {
+                            Ok(term)
+                        }
+                    } else {
+                        Err(String::from("Expected lambda body after arrow"))
+                    }
+                }
+                Some((_, Tree::Colon)) => {
+                    if let Some((tree_index, tree)) = branch.next() {
+                        locator.mark(index);
+                        let term = def(*ch, construct_node(tree, tree_index, locator)?);
                         if branch.next().is_some() {
                             Err(String::from("Definition has too many terms"))
                         } else {
@@ -78,26 +313,32 @@ fn construct_branch(branch: &[Tree]) -> Result<Term, String> {
                         Err(String::from("Expected term after colon"))
                     }
                 }
-                Some(Tree::Name(ch2)) => {
+                Some((node2_index, Tree::Name(ch2))) => {
                     if branch.next().is_some() {
                         Err(String::from("Application has too many terms"))
                     } else {
+                        locator.mark(index);
+                        locator.mark(node_index);
+                        locator.mark(node2_index);
                         Ok(app(var(*ch), var(*ch2)))
                     }
                 }
-                Some(Tree::Branch(_)) => {
+                Some((node2_index, node2 @ Tree::Branch(_))) => {
                     if branch.next().is_some() {
                         Err(String::from("Application has too many terms"))
                     } else {
-                        Ok(app(var(*ch), construct(node2.unwrap())?))
+                        locator.mark(index);
+                        locator.mark(node_index);
+                        Ok(app(var(*ch), construct_node(node2, node2_index, locator)?))
                     }
                 }
             }
         }
         Tree::Branch(_) => {
-            let func = construct(node)?;
-            let arg = if let Some(node) = branch.next() {
-                construct(node)?
+            locator.mark(index);
+            let func = construct_node(node, node_index, locator)?;
+            let arg = if let Some((arg_index, node)) = branch.next() {
+                construct_node(node, arg_index, locator)?
             } else {
                 return Err(String::from("Expected application argument"));
             };
@@ -105,12 +346,51 @@ fn construct_branch(branch: &[Tree]) -> Result<Term, String> {
         }
         Tree::Arrow => Err(String::from("Unexpected arrow")),
         Tree::Colon => Err(String::from("Unexpected colon")),
+        Tree::Assert => Err(String::from("Assertions are only allowed at the top level")),
+        Tree::Equals => Err(String::from("Unexpected '=='")),
+        Tree::Let | Tree::Rec | Tree::In | Tree::Where | Tree::Bind => Err(misplaced(node)),
+        Tree::Bracket(_) => Err(String::from("Unexpected type annotation")),
+    }
+}
+
+pub fn construct_type(trees: &[Tree]) -> Result<Type, String> {
+    match trees {
+        [] => Err(String::from("Expected type")),
+        [tree] => construct_type_atom(tree),
+        [Tree::Bracket(vars), Tree::Arrow, rest @ ..] => match vars.as_slice() {
+            [Tree::Name(name)] => Ok(types::forall(*name, construct_type(rest)?)),
+            _ => Err(String::from("Expected one type variable in brackets")),
+        },
+        [tree, Tree::Arrow, rest @ ..] => Ok(types::arrow(
+            construct_type_atom(tree)?,
+            construct_type(rest)?,
+        )),
+        [_, _, ..] => Err(String::from("Expected arrow between types")),
+    }
+}
+
+fn construct_type_atom(tree: &Tree) -> Result<Type, String> {
+    match tree {
+        Tree::Name(ch) => Ok(types::var(*ch)),
+        Tree::Branch(branch) => construct_type(branch),
+        Tree::Arrow => Err(String::from("Unexpected arrow in type")),
+        Tree::Colon => Err(String::from("Unexpected colon in type")),
+        Tree::Assert
+        | Tree::Equals
+        | Tree::Let
+        | Tree::Rec
+        | Tree::In
+        | Tree::Where
+        | Tree::Bind => Err(String::from("Unexpected keyword in type")),
+        Tree::Bracket(_) => Err(String::from("Unexpected bracket in type")),
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::lex;
+    use crate::parse;
     use crate::pipeline;
     use crate::pipeline::Pipeline;
     use crate::pipeline::PipelineResult;
@@ -157,5 +437,123 @@ mod test {
         err!("(x y z)", String::from("Application has too many terms"));
         err!("((a -> b))", String::from("Expected application argument"));
         err!("(-> x)", String::from("Unexpected arrow"));
+        err!("(x -> ->)", String::from("Unexpected arrow"));
+        err!("(x -> :)", String::from("Unexpected colon"));
+        ok!("(x [a] -> x)", typed_lambda('x', types::var('a'), var('x')));
+        ok!(
+            "(f [(a -> b) -> a -> b] -> f)",
+            typed_lambda(
+                'f',
+                types::arrow(
+                    types::arrow(types::var('a'), types::var('b')),
+                    types::arrow(types::var('a'), types::var('b'))
+                ),
+                var('f')
+            )
+        );
+        err!("(x [] -> x)", String::from("Expected type"));
+        err!(
+            "(x [a b] -> x)",
+            String::from("Expected arrow between types")
+        );
+        err!("(x [a -> ] -> x)", String::from("Expected type"));
+        err!(
+            "(x [a] x)",
+            String::from("Expected arrow after type annotation")
+        );
+        ok!(
+            "(x [[a] -> a -> a] -> x)",
+            typed_lambda(
+                'x',
+                types::forall('a', types::arrow(types::var('a'), types::var('a'))),
+                var('x')
+            )
+        );
+        err!(
+            "(x [[a b] -> a] -> x)",
+            String::from("Expected one type variable in brackets")
+        );
+
+        ok!("(let x = y in x)", let_in('x', var('y'), var('x')));
+        ok!(
+            "((f x) where f = g)",
+            let_in('f', var('g'), app(var('f'), var('x')))
+        );
+        ok!(
+            "(let rec f = (x -> f) in f)",
+            let_rec(
+                'f',
+                app(fixpoint(), lambda('f', lambda('x', var('f')))),
+                var('f')
+            )
+        );
+        err!(
+            "(let x = y in)",
+            String::from("Expected `(let x = a in b)`")
+        );
+        err!(
+            "(let x = y z in x)",
+            String::from("Expected `(let x = a in b)`")
+        );
+        err!(
+            "(x where y = z w)",
+            String::from("Expected `(b where x = a)`")
+        );
+        err!("(x -> (where x = y))", String::from("Unexpected `where`"));
+        err!("(x = y)", String::from("Unexpected '='"));
+    }
+
+    #[test]
+    fn spans() {
+        let tokens = lex::lex_spanned("((x -> (f x)) y)").unwrap();
+        let tree = parse::parse(&lex::lex("((x -> (f x)) y)").unwrap()).unwrap();
+        let (_, spans) = construct_located(&tree, &parse::spans(&tokens)).unwrap();
+        let starts: Vec<usize> = spans.iter().map(|span| span.start).collect();
+        assert_eq!(starts, vec![0, 1, 7, 8, 10, 14]);
+        assert_eq!(spans[1], Span { start: 1, end: 13 });
+
+        let text = "(y where y = (x -> x))";
+        let tokens = lex::lex_spanned(text).unwrap();
+        let tree = parse::parse(&lex::lex(text).unwrap()).unwrap();
+        let (_, spans) = construct_located(&tree, &parse::spans(&tokens)).unwrap();
+        let starts: Vec<usize> = spans.iter().map(|span| span.start).collect();
+        assert_eq!(starts, vec![0, 13, 19, 1]);
+        assert_eq!(spans[0], Span { start: 0, end: 22 });
+    }
+
+    #[test]
+    fn recovering() {
+        let (fragments, errors) = pipeline::recover("((x -> y z) (f (g)))\n(h -> (h h))").unwrap();
+        let errors: Vec<(usize, String)> = errors
+            .into_iter()
+            .map(|(span, err)| (span.start, err))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, String::from("Lambda body has too many terms")),
+                (15, String::from("Expected more symbols after name")),
+            ]
+        );
+        let terms: Vec<String> = fragments.iter().map(|(term, _)| term.to_string()).collect();
+        assert_eq!(terms, vec!["(h -> (h h))"]);
+        assert_eq!(fragments[0].1[0], Span { start: 21, end: 33 });
+
+        let (fragments, errors) = pipeline::recover("(f -> (x) (y -> y))").unwrap();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    Span { start: 0, end: 19 },
+                    String::from("Lambda body has too many terms")
+                ),
+                (
+                    Span { start: 6, end: 9 },
+                    String::from("Expected more symbols after name")
+                ),
+            ]
+        );
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].0.to_string(), "(y -> y)");
     }
 }
@@ -0,0 +1,110 @@
+use crate::construct::Term;
+
+/// Nameless form of a term. Free variables keep their names.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeBruijn {
+    Bound(usize),
+    Free(char),
+    Lambda(Box<DeBruijn>),
+    App(Box<DeBruijn>, Box<DeBruijn>),
+    Def(Box<DeBruijn>),
+    Let(Box<DeBruijn>, Box<DeBruijn>),
+}
+
+pub fn debruijn(term: &Term) -> DeBruijn {
+    do_debruijn(term, &mut Vec::new())
+}
+
+pub fn alpha_equivalent(term1: &Term, term2: &Term) -> bool {
+    debruijn(term1) == debruijn(term2)
+}
+
+fn do_debruijn(term: &Term, scope: &mut Vec<(char, Option<usize>)>) -> DeBruijn {
+    match term {
+        Term::Var(name, id) => {
+            let binder =
+                scope
+                    .iter()
+                    .rev()
+                    .position(|(parent_name, parent_id)| match (id, parent_id) {
+                        (Some(id), Some(parent_id)) => id == parent_id,
+                        _ => name == parent_name,
+                    });
+            match binder {
+                Some(index) => DeBruijn::Bound(index),
+                None => DeBruijn::Free(*name),
+            }
+        }
+        Term::Lambda(name, id, _, body) => {
+            scope.push((*name, *id));
+            let body = do_debruijn(body, scope);
+            scope.pop();
+            DeBruijn::Lambda(Box::new(body))
+        }
+        Term::Def(name, id, body) => {
+            scope.push((*name, *id));
+            let body = do_debruijn(body, scope);
+            scope.pop();
+            DeBruijn::Def(Box::new(body))
+        }
+        Term::Let(name, id, _, value, body) => {
+            let value = do_debruijn(value, scope);
+            scope.push((*name, *id));
+            let body = do_debruijn(body, scope);
+            scope.pop();
+            DeBruijn::Let(Box::new(value), Box::new(body))
+        }
+        Term::App(func, arg) => DeBruijn::App(
+            Box::new(do_debruijn(func, scope)),
+            Box::new(do_debruijn(arg, scope)),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline::define;
+
+    macro_rules! run {
+        ($text:literal, $expected:expr) => {
+            assert_eq!(debruijn(&define($text)), $expected);
+        };
+    }
+
+    macro_rules! alpha {
+        ($text1:literal, $text2:literal, $expected:expr) => {
+            assert_eq!(
+                alpha_equivalent(&define($text1), &define($text2)),
+                $expected
+            );
+        };
+    }
+
+    #[test]
+    fn test() {
+        run!("x", DeBruijn::Free('x'));
+        run!("(x -> x)", DeBruijn::Lambda(Box::new(DeBruijn::Bound(0))));
+        run!(
+            "(x -> (y -> x))",
+            DeBruijn::Lambda(Box::new(DeBruijn::Lambda(Box::new(DeBruijn::Bound(1)))))
+        );
+        run!(
+            "(x -> (x -> x))",
+            DeBruijn::Lambda(Box::new(DeBruijn::Lambda(Box::new(DeBruijn::Bound(0)))))
+        );
+        run!(
+            "(x -> (f x))",
+            DeBruijn::Lambda(Box::new(DeBruijn::App(
+                Box::new(DeBruijn::Free('f')),
+                Box::new(DeBruijn::Bound(0))
+            )))
+        );
+        alpha!("(x -> x)", "(y -> y)", true);
+        alpha!("(x -> (y -> x))", "(a -> (b -> a))", true);
+        alpha!("(x -> (y -> x))", "(a -> (b -> b))", false);
+        alpha!("(x -> y)", "(a -> y)", true);
+        alpha!("(x -> y)", "(a -> z)", false);
+        alpha!("(f f)", "(f f)", true);
+    }
+}
@@ -5,20 +5,93 @@ pub fn define(term: &mut Term) {
     define_lambdas(term, &mut id_counter);
 }
 
+/// The id `define` gave a variable or binder.
+pub fn id(id: Option<usize>) -> Result<usize, String> {
+    id.ok_or_else(|| String::from("Term has not been defined"))
+}
+
+/// One more than the largest id in `term`.
+pub fn next_id(term: &Term) -> usize {
+    match term {
+        Term::Var(_, id) => id.map_or(0, |id| id + 1),
+        Term::Lambda(_, id, _, body) | Term::Def(_, id, body) => {
+            id.map_or(0, |id| id + 1).max(next_id(body))
+        }
+        Term::App(func, arg) => next_id(func).max(next_id(arg)),
+        Term::Let(_, id, _, value, body) => id
+            .map_or(0, |id| id + 1)
+            .max(next_id(value))
+            .max(next_id(body)),
+    }
+}
+
+/// Gives every binder in `term` a new id from `next_id`.
+pub fn freshen(term: &mut Term, next_id: &mut usize) {
+    freshen_scoped(term, next_id, &mut Vec::new());
+}
+
+fn freshen_scoped(term: &mut Term, next_id: &mut usize, scope: &mut Vec<(usize, usize)>) {
+    match term {
+        Term::Var(_, id) => {
+            if let Some((_, fresh)) = scope.iter().rev().find(|(old, _)| Some(*old) == *id) {
+                *id = Some(*fresh);
+            }
+        }
+        Term::Lambda(_, id, _, body) | Term::Def(_, id, body) => {
+            let bound = rebind(id, next_id, scope);
+            freshen_scoped(body, next_id, scope);
+            if bound {
+                scope.pop();
+            }
+        }
+        Term::App(func, arg) => {
+            freshen_scoped(func, next_id, scope);
+            freshen_scoped(arg, next_id, scope);
+        }
+        Term::Let(_, id, _, value, body) => {
+            freshen_scoped(value, next_id, scope);
+            let bound = rebind(id, next_id, scope);
+            freshen_scoped(body, next_id, scope);
+            if bound {
+                scope.pop();
+            }
+        }
+    }
+}
+
+fn rebind(id: &mut Option<usize>, next_id: &mut usize, scope: &mut Vec<(usize, usize)>) -> bool {
+    match id {
+        Some(old) => {
+            scope.push((*old, *next_id));
+            *id = Some(*next_id);
+            *next_id += 1;
+            true
+        }
+        None => false,
+    }
+}
+
 fn define_lambdas(term: &mut Term, id_counter: &mut usize) {
     match term {
-        Term::Lambda(name, id, term) => {
+        Term::Lambda(name, id, _, term) => {
+            define_body(term, *name, *id_counter);
             *id = Some(*id_counter);
             *id_counter += 1;
-            define_body(term, *name, id.unwrap());
             define_lambdas(term, id_counter);
         }
         Term::Def(name, id, term) => {
+            define_body(term, *name, *id_counter);
             *id = Some(*id_counter);
             *id_counter += 1;
-            define_body(term, *name, id.unwrap());
             define_lambdas(term, id_counter);
         }
+        Term::Let(name, id, _, value, body) => {
+            define_body(body, *name, *id_counter);
+            *id = Some(*id_counter);
+            *id_counter += 1;
+            define_lambdas(value, id_counter);
+            define_lambdas(body, id_counter);
+        }
         Term::App(term1, term2) => {
             define_lambdas(term1, id_counter);
             define_lambdas(term2, id_counter);
@@ -34,13 +107,16 @@ fn define_lambdas(term: &mut Term, id_counter: &mut usize) {
 
 fn define_body(term: &mut Term, parent_name: char, parent_id: usize) {
     match term {
-        Term::Lambda(name, _, term) => {
+        Term::Lambda(name, _, _, term) | Term::Def(name, _, term) => {
             if *name != parent_name {
                 define_body(term, parent_name, parent_id)
             }
         }
-        Term::Def(_, _, _) => {
-            panic!();
+        Term::Let(name, _, _, value, body) => {
+            define_body(value, parent_name, parent_id);
+            if *name != parent_name {
+                define_body(body, parent_name, parent_id)
+            }
         }
         Term::App(term1, term2) => {
             define_body(term1, parent_name, parent_id);
@@ -59,6 +135,7 @@ mod test {
     use crate::construct::app;
     use crate::construct::lambda_id;
     use crate::construct::var_id;
+    use crate::construct::Term;
     use crate::pipeline;
     use crate::pipeline::Pipeline;
     use crate::pipeline::PipelineResult;
@@ -121,5 +198,13 @@ mod test {
                 lambda_id('x', 1, app(var_id('x', 1), var_id('x', 1)))
             )
         );
+        run!(
+            "(x -> (f : (f x)))",
+            lambda_id(
+                'x',
+                0,
+                Term::Def('f', Some(1), Box::new(app(var_id('f', 1), var_id('x', 0))))
+            )
+        );
     }
 }
@@ -0,0 +1,227 @@
+use crate::construct;
+use crate::construct::Term;
+use crate::debruijn;
+use crate::debruijn::DeBruijn;
+use crate::define;
+use crate::eval;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+/// Renders a defined term as a syntax tree with edges to binders.
+pub fn ast(term: &Term) -> String {
+    let mut out = String::from("digraph ast {\n");
+    let mut binders = HashMap::new();
+    let mut next = 0;
+    ast_node(term, &mut out, &mut binders, &mut next);
+    out.push_str("}\n");
+    out
+}
+
+fn ast_node(
+    term: &Term,
+    out: &mut String,
+    binders: &mut HashMap<usize, usize>,
+    next: &mut usize,
+) -> usize {
+    let node = *next;
+    *next += 1;
+    match term {
+        Term::Var(name, id) => {
+            writeln!(out, "  n{} [label=\"{}\"];", node, name).unwrap();
+            if let Some(binder) = id.and_then(|id| binders.get(&id)) {
+                writeln!(
+                    out,
+                    "  n{} -> n{} [style=dashed, constraint=false];",
+                    node, binder
+                )
+                .unwrap();
+            }
+        }
+        Term::Lambda(name, id, _, body) | Term::Def(name, id, body) => {
+            let label = match term {
+                Term::Lambda(..) => format!("λ{}", name),
+                _ => format!("{} :", name),
+            };
+            writeln!(out, "  n{} [label=\"{}\"];", node, label).unwrap();
+            if let Some(id) = id {
+                binders.insert(*id, node);
+            }
+            let child = ast_node(body, out, binders, next);
+            writeln!(out, "  n{} -> n{};", node, child).unwrap();
+        }
+        Term::Let(name, id, _, value, body) => {
+            writeln!(out, "  n{} [label=\"let {}\"];", node, name).unwrap();
+            let value = ast_node(value, out, binders, next);
+            if let Some(id) = id {
+                binders.insert(*id, node);
+            }
+            let body = ast_node(body, out, binders, next);
+            writeln!(out, "  n{} -> n{};", node, value).unwrap();
+            writeln!(out, "  n{} -> n{};", node, body).unwrap();
+        }
+        Term::App(func, arg) => {
+            writeln!(out, "  n{} [label=\"@\"];", node).unwrap();
+            let func = ast_node(func, out, binders, next);
+            let arg = ast_node(arg, out, binders, next);
+            writeln!(out, "  n{} -> n{};", node, func).unwrap();
+            writeln!(out, "  n{} -> n{};", node, arg).unwrap();
+        }
+    }
+    node
+}
+
+/// Renders the reduction graph of `term`, exploring at most `bound` terms.
+pub fn reductions(term: &Term, bound: usize) -> Result<String, String> {
+    let mut out = String::from("digraph reductions {\n");
+    let mut nodes: HashMap<DeBruijn, usize> = HashMap::new();
+    let mut queue = VecDeque::new();
+    nodes.insert(debruijn::debruijn(term), 0);
+    queue.push_back((0, term.clone()));
+    let mut explored = 0;
+    while let Some((node, term)) = queue.pop_front() {
+        let reducts = reducts(&term, define::next_id(&term))?;
+        let style = if reducts.is_empty() {
+            ", peripheries=2"
+        } else if explored >= bound {
+            ", style=dashed"
+        } else {
+            ""
+        };
+        writeln!(out, "  t{} [label=\"{}\"{}];", node, term, style).unwrap();
+        if explored >= bound {
+            continue;
+        }
+        explored += 1;
+        let mut edges = HashSet::new();
+        for reduct in reducts {
+            let key = debruijn::debruijn(&reduct);
+            let next = match nodes.get(&key) {
+                Some(next) => *next,
+                None => {
+                    let next = nodes.len();
+                    nodes.insert(key, next);
+                    queue.push_back((next, reduct));
+                    next
+                }
+            };
+            if edges.insert(next) {
+                writeln!(out, "  t{} -> t{};", node, next).unwrap();
+            }
+        }
+    }
+    out.push_str("}\n");
+    Ok(out)
+}
+
+/// Every term one redex away, in order of the redexes.
+fn reducts(term: &Term, next_id: usize) -> Result<Vec<Term>, String> {
+    let mut reducts = Vec::new();
+    match term {
+        Term::App(func, arg) => {
+            if let Term::Lambda(_, Some(id), _, body) = &**func {
+                let mut body = (**body).clone();
+                let mut fresh = next_id;
+                eval::substitute_var(*id, &mut body, arg, &mut fresh)?;
+                reducts.push(body);
+            }
+            for func in self::reducts(func, next_id)? {
+                reducts.push(Term::App(Box::new(func), arg.clone()));
+            }
+            for arg in self::reducts(arg, next_id)? {
+                reducts.push(Term::App(func.clone(), Box::new(arg)));
+            }
+        }
+        Term::Lambda(name, id, ty, body) => {
+            for body in self::reducts(body, next_id)? {
+                reducts.push(Term::Lambda(*name, *id, ty.clone(), Box::new(body)));
+            }
+        }
+        Term::Def(name, id, body) => {
+            for body in self::reducts(body, next_id)? {
+                reducts.push(Term::Def(*name, *id, Box::new(body)));
+            }
+        }
+        Term::Let(name, id, _, value, body) => {
+            return self::reducts(
+                &construct::redex(*name, *id, (**value).clone(), (**body).clone()),
+                next_id,
+            )
+        }
+        Term::Var(..) => (),
+    }
+    Ok(reducts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline::define;
+
+    #[test]
+    fn test() {
+        assert_eq!(
+            ast(&define("(x -> (f x))")),
+            "digraph ast {
+  n0 [label=\"λx\"];
+  n1 [label=\"@\"];
+  n2 [label=\"f\"];
+  n3 [label=\"x\"];
+  n3 -> n0 [style=dashed, constraint=false];
+  n1 -> n2;
+  n1 -> n3;
+  n0 -> n1;
+}
+"
+        );
+    }
+
+    #[test]
+    fn reduction_graph() {
+        assert_eq!(
+            reductions(&define("((x -> (f x)) ((y -> y) z))"), 10).unwrap(),
+            "digraph reductions {
+  t0 [label=\"((x -> (f x)) ((y -> y) z))\"];
+  t0 -> t1;
+  t0 -> t2;
+  t1 [label=\"(f ((y -> y) z))\"];
+  t1 -> t3;
+  t2 [label=\"((x -> (f x)) z)\"];
+  t2 -> t3;
+  t3 [label=\"(f z)\", peripheries=2];
+}
+"
+        );
+        assert_eq!(
+            reductions(&define("((x -> (x x)) (x -> (x x)))"), 10).unwrap(),
+            "digraph reductions {
+  t0 [label=\"((x -> (x x)) (x -> (x x)))\"];
+  t0 -> t0;
+}
+"
+        );
+        assert_eq!(
+            reductions(&define("((x -> (f x)) ((y -> y) z))"), 1).unwrap(),
+            "digraph reductions {
+  t0 [label=\"((x -> (f x)) ((y -> y) z))\"];
+  t0 -> t1;
+  t0 -> t2;
+  t1 [label=\"(f ((y -> y) z))\", style=dashed];
+  t2 [label=\"((x -> (f x)) z)\", style=dashed];
+}
+"
+        );
+        assert_eq!(
+            reductions(&define("((x -> x) ((y -> y) z))"), 10).unwrap(),
+            "digraph reductions {
+  t0 [label=\"((x -> x) ((y -> y) z))\"];
+  t0 -> t1;
+  t1 [label=\"((y -> y) z)\"];
+  t1 -> t2;
+  t2 [label=\"z\", peripheries=2];
+}
+"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use crate::construct::Term;
+use crate::free;
+
+/// Eta-reduces `term`, returning the number of eta steps taken.
+pub fn reduce(term: &mut Term) -> usize {
+    let mut steps = 0;
+    while step(term) {
+        steps += 1;
+    }
+    steps
+}
+
+/// Contracts the leftmost outermost eta redex, if there is one.
+pub fn step(term: &mut Term) -> bool {
+    if let Some(func) = redex(term) {
+        *term = func;
+        return true;
+    }
+    match term {
+        Term::Var(..) => false,
+        Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => step(body),
+        Term::App(func, arg) | Term::Let(_, _, _, func, arg) => step(func) || step(arg),
+    }
+}
+
+fn redex(term: &Term) -> Option<Term> {
+    if let Term::Lambda(_, Some(id), _, body) = term {
+        if let Term::App(func, arg) = &**body {
+            if let Term::Var(_, Some(arg_id)) = **arg {
+                if arg_id == *id && !occurs(*id, func) {
+                    return Some((**func).clone());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Rewrites `f` to `(x -> (f x))` with `x` not free in `f`.
+pub fn expand(term: &mut Term) -> Result<(), String> {
+    let free = free::free_names(term);
+    let name = ('a'..='z')
+        .find(|name| !mentions(*name, term))
+        .or_else(|| ('a'..='z').find(|name| !free.contains(name)))
+        .ok_or_else(|| String::from("Ran out of names to eta-expand with"))?;
+    let id = max_id(term).map_or(0, |id| id + 1);
+    let func = term.clone();
+    *term = Term::Lambda(
+        name,
+        Some(id),
+        None,
+        Box::new(Term::App(
+            Box::new(func),
+            Box::new(Term::Var(name, Some(id))),
+        )),
+    );
+    Ok(())
+}
+
+fn occurs(id: usize, term: &Term) -> bool {
+    match term {
+        Term::Var(_, var_id) => *var_id == Some(id),
+        Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => occurs(id, body),
+        Term::App(func, arg) | Term::Let(_, _, _, func, arg) => occurs(id, func) || occurs(id, arg),
+    }
+}
+
+fn mentions(name: char, term: &Term) -> bool {
+    match term {
+        Term::Var(var_name, _) => *var_name == name,
+        Term::Lambda(binder, _, _, body) | Term::Def(binder, _, body) => {
+            *binder == name || mentions(name, body)
+        }
+        Term::App(func, arg) => mentions(name, func) || mentions(name, arg),
+        Term::Let(binder, _, _, value, body) => {
+            *binder == name || mentions(name, value) || mentions(name, body)
+        }
+    }
+}
+
+fn max_id(term: &Term) -> Option<usize> {
+    match term {
+        Term::Var(_, id) => *id,
+        Term::Lambda(_, id, _, body) | Term::Def(_, id, body) => (*id).max(max_id(body)),
+        Term::App(func, arg) => max_id(func).max(max_id(arg)),
+        Term::Let(_, id, _, value, body) => (*id).max(max_id(value)).max(max_id(body)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::construct;
+    use crate::construct::app;
+    use crate::construct::lambda_id;
+    use crate::construct::var;
+    use crate::construct::var_id;
+    use crate::define;
+    use crate::pipeline;
+    use crate::pipeline::Pipeline;
+    use crate::pipeline::PipelineResult;
+
+    macro_rules! reduce {
+        ($text:literal, $steps:expr, $expected:expr) => {
+            if let Ok(PipelineResult::Term(mut term)) = pipeline::pipeline(Pipeline::Define, $text)
+            {
+                assert_eq!(reduce(&mut term), $steps);
+                assert_eq!(term, $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    macro_rules! expand {
+        ($text:literal, $expected:expr) => {
+            if let Ok(PipelineResult::Term(mut term)) = pipeline::pipeline(Pipeline::Define, $text)
+            {
+                assert_eq!(expand(&mut term), Ok(()));
+                assert_eq!(term, $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    #[test]
+    fn test() {
+        reduce!("(x -> (f x))", 1, var_id('f', 1));
+        reduce!(
+            "(x -> (x x))",
+            0,
+            lambda_id('x', 0, app(var_id('x', 0), var_id('x', 0)))
+        );
+        reduce!(
+            "(x -> ((f x) x))",
+            0,
+            lambda_id(
+                'x',
+                0,
+                app(app(var_id('f', 1), var_id('x', 0)), var_id('x', 0))
+            )
+        );
+        reduce!("(x -> (y -> ((f x) y)))", 2, var_id('f', 2));
+        reduce!("(g (x -> (f x)))", 1, app(var_id('g', 0), var_id('f', 2)));
+        reduce!("(x -> (y -> (x y)))", 1, lambda_id('x', 0, var_id('x', 0)));
+
+        let mut undefined = construct::lambda('x', app(var('f'), var('x')));
+        assert_eq!(reduce(&mut undefined), 0);
+        expand!("f", lambda_id('a', 1, app(var_id('f', 0), var_id('a', 1))));
+        expand!(
+            "(a -> b)",
+            lambda_id(
+                'c',
+                2,
+                app(lambda_id('a', 0, var_id('b', 1)), var_id('c', 2))
+            )
+        );
+
+        let mut term = ('b'..='z').fold(construct::lambda('a', var('a')), |term, name| {
+            app(term, var(name))
+        });
+        define::define(&mut term);
+        assert_eq!(expand(&mut term), Ok(()));
+        assert!(matches!(term, Term::Lambda('a', Some(26), None, _)));
+        let mut term = ('a'..='z').fold(var('f'), |term, name| app(term, var(name)));
+        define::define(&mut term);
+        assert_eq!(
+            expand(&mut term),
+            Err(String::from("Ran out of names to eta-expand with"))
+        );
+    }
+}
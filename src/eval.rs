@@ -1,58 +1,249 @@
+use crate::construct;
 use crate::construct::Term;
+use crate::debruijn;
+use crate::debruijn::DeBruijn;
+use crate::define;
+use crate::eta;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
 
 const RECURSION_LIMIT: usize = 1000;
+const CYCLE_WINDOW: usize = 64;
 
-pub fn eval(term: &mut Term) -> Result<(), String> {
+/// Where reduction stops: weak head, head or full normal form.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Mode {
+    WeakHead,
+    Head,
+    #[default]
+    Normal,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    pub eta: bool,
+    pub mode: Mode,
+    /// Most beta steps to take before giving up.
+    pub limit: Option<usize>,
+}
+
+/// Measurements taken while reducing a term to normal form.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    pub beta_steps: usize,
+    pub eta_steps: usize,
+    pub max_size: usize,
+    pub max_depth: usize,
+    pub substitutions: usize,
+    pub elapsed: Duration,
+}
+
+/// Reduces `term` to normal form, returning the number of beta steps taken.
+pub fn eval(term: &mut Term) -> Result<usize, String> {
+    Ok(eval_stats(term)?.beta_steps)
+}
+
+pub fn eval_stats(term: &mut Term) -> Result<Stats, String> {
+    eval_with(term, &Options::default())
+}
+
+pub fn eval_with(term: &mut Term, options: &Options) -> Result<Stats, String> {
+    trace(term, options, &mut |_| ())
+}
+
+/// Like `eval_with`, calling `step` with each term along the way.
+pub fn trace(
+    term: &mut Term,
+    options: &Options,
+    step: &mut dyn FnMut(&Term),
+) -> Result<Stats, String> {
+    let start = Instant::now();
+    let mut stats = Stats::default();
+    let mut history = History::default();
+    let mut next_id = define::next_id(term);
     loop {
+        step(term);
+        history.visit(term, stats.beta_steps)?;
+        stats.max_size = stats.max_size.max(construct::size(term));
+        stats.max_depth = stats.max_depth.max(depth(term));
         let mut modified = false;
         let mut recursion = 0;
-        do_eval(term, &mut modified, &mut recursion)?;
+        do_eval(
+            term,
+            options.mode,
+            &mut modified,
+            &mut recursion,
+            &mut stats.substitutions,
+            &mut next_id,
+        )?;
         if !modified {
-            return Ok(());
+            // Eta steps never make a beta redex in a beta normal form, so
+            // contracting them one at a time here reaches the beta-eta
+            // normal form.
+            if options.eta && options.mode == Mode::Normal && eta::step(term) {
+                stats.eta_steps += 1;
+                continue;
+            }
+            stats.elapsed = start.elapsed();
+            return Ok(stats);
+        }
+        if options.limit == Some(stats.beta_steps) {
+            return Err(String::from("Hit step limit"));
         }
+        stats.beta_steps += 1;
     }
 }
 
-fn do_eval(term: &mut Term, modified: &mut bool, recursion: &mut usize) -> Result<(), String> {
+/// Whether both terms have alpha-equivalent beta-eta normal forms.
+pub fn equivalent(term1: &Term, term2: &Term) -> Result<bool, String> {
+    let options = Options {
+        eta: true,
+        mode: Mode::Normal,
+        limit: None,
+    };
+    let mut term1 = term1.clone();
+    let mut term2 = term2.clone();
+    eval_with(&mut term1, &options)?;
+    eval_with(&mut term2, &options)?;
+    Ok(debruijn::alpha_equivalent(&term1, &term2))
+}
+
+/// Recently visited states in nameless form, to detect loops.
+#[derive(Default)]
+struct History {
+    seen: HashMap<DeBruijn, usize>,
+    order: VecDeque<DeBruijn>,
+}
+
+impl History {
+    fn visit(&mut self, term: &Term, step: usize) -> Result<(), String> {
+        let state = debruijn::debruijn(term);
+        if let Some(previous) = self.seen.get(&state) {
+            return Err(format!("diverges: cycle of length {}", step - previous));
+        }
+        if self.order.len() == CYCLE_WINDOW {
+            let oldest = self.order.pop_front().unwrap();
+            self.seen.remove(&oldest);
+        }
+        self.seen.insert(state.clone(), step);
+        self.order.push_back(state);
+        Ok(())
+    }
+}
+
+pub fn depth(term: &Term) -> usize {
+    match term {
+        Term::Var(..) => 1,
+        Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => 1 + depth(body),
+        Term::App(func, arg) | Term::Let(_, _, _, func, arg) => 1 + depth(func).max(depth(arg)),
+    }
+}
+
+fn do_eval(
+    term: &mut Term,
+    mode: Mode,
+    modified: &mut bool,
+    recursion: &mut usize,
+    substitutions: &mut usize,
+    next_id: &mut usize,
+) -> Result<(), String> {
     bump_recursion_count(recursion)?;
     match term {
         Term::App(func, ref mut arg) => {
-            if let Term::Lambda(_, id, ref mut body) = **func {
-                substitute(id.unwrap(), body, arg, modified, recursion)?;
+            if let Term::Lambda(_, id, _, ref mut body) = **func {
+                substitute(
+                    define::id(id)?,
+                    body,
+                    arg,
+                    &mut false,
+                    recursion,
+                    substitutions,
+                    next_id,
+                )?;
                 *term = *body.clone();
                 *modified = true;
-            } else if let Term::App { .. } = **func {
-                do_eval(func, modified, recursion)?
-            } else if let Term::App { .. } = **arg {
-                do_eval(arg, modified, recursion)?
-            } else if let Term::Lambda { .. } = **arg {
-                do_eval(arg, modified, recursion)?
+            } else {
+                let func_mode = match mode {
+                    Mode::Normal => Mode::Normal,
+                    Mode::Head | Mode::WeakHead => Mode::WeakHead,
+                };
+                do_eval(func, func_mode, modified, recursion, substitutions, next_id)?;
+                if !*modified && mode == Mode::Normal {
+                    do_eval(arg, mode, modified, recursion, substitutions, next_id)?;
+                }
             }
             Ok(())
         }
-        Term::Lambda(_, _, body) => do_eval(body, modified, recursion),
+        Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => match mode {
+            Mode::WeakHead => Ok(()),
+            Mode::Head | Mode::Normal => {
+                do_eval(body, mode, modified, recursion, substitutions, next_id)
+            }
+        },
+        Term::Let(name, id, _, value, body) => {
+            *term = construct::redex(*name, *id, (**value).clone(), (**body).clone());
+            do_eval(term, mode, modified, recursion, substitutions, next_id)
+        }
         Term::Var { .. } => Ok(()),
     }
 }
 
+/// Replaces the variables bound by `id` in `body` with `arg`, taking the
+/// binder ids of any extra copies from `next_id`.
+pub fn substitute_var(
+    id: usize,
+    body: &mut Term,
+    arg: &Term,
+    next_id: &mut usize,
+) -> Result<(), String> {
+    substitute(id, body, arg, &mut false, &mut 0, &mut 0, next_id)
+}
+
 fn substitute(
     id: usize,
     body: &mut Term,
-    arg: &mut Term,
-    modified: &mut bool,
+    arg: &Term,
+    copied: &mut bool,
     recursion: &mut usize,
+    substitutions: &mut usize,
+    next_id: &mut usize,
 ) -> Result<(), String> {
     bump_recursion_count(recursion)?;
     match body {
         Term::App(func, app_arg) => {
-            substitute(id, func, arg, modified, recursion)?;
-            substitute(id, app_arg, arg, modified, recursion)
+            substitute(id, func, arg, copied, recursion, substitutions, next_id)?;
+            substitute(id, app_arg, arg, copied, recursion, substitutions, next_id)
+        }
+        // A copy of the binder made by an earlier substitution binds its own
+        // occurrences.
+        Term::Lambda(_, binder, _, _) | Term::Def(_, binder, _) if *binder == Some(id) => Ok(()),
+        Term::Lambda(_, _, _, ref mut body) => {
+            substitute(id, body, arg, copied, recursion, substitutions, next_id)
+        }
+        Term::Def(_, _, ref mut body) => {
+            substitute(id, body, arg, copied, recursion, substitutions, next_id)
+        }
+        Term::Let(_, binder, _, value, body) => {
+            substitute(id, value, arg, copied, recursion, substitutions, next_id)?;
+            if *binder == Some(id) {
+                Ok(())
+            } else {
+                substitute(id, body, arg, copied, recursion, substitutions, next_id)
+            }
         }
-        Term::Lambda(_, _, ref mut body) => substitute(id, body, arg, modified, recursion),
         Term::Var(_, child_id) => {
-            if child_id.unwrap() == id {
+            if *child_id == Some(id) {
+                // Copies after the first get binders of their own, so that
+                // binder ids stay distinct and no copy captures another's
+                // variables.
                 *body = arg.clone();
-                *modified = true;
+                if *copied {
+                    define::freshen(body, next_id);
+                }
+                *copied = true;
+                *substitutions += 1;
             }
             Ok(())
         }
@@ -74,6 +265,7 @@ mod test {
     use crate::construct::app;
     use crate::construct::lambda_id;
     use crate::construct::var_id;
+    use crate::debruijn;
     use crate::pipeline;
     use crate::pipeline::Pipeline;
     use crate::pipeline::PipelineResult;
@@ -92,7 +284,16 @@ mod test {
         ($text:literal, $expected:expr) => {
             if let Ok(PipelineResult::Term(mut term)) = pipeline::pipeline(Pipeline::Define, $text)
             {
-                do_eval(&mut term, &mut false, &mut 0).unwrap();
+                let mut next_id = define::next_id(&term);
+                do_eval(
+                    &mut term,
+                    Mode::Normal,
+                    &mut false,
+                    &mut 0,
+                    &mut 0,
+                    &mut next_id,
+                )
+                .unwrap();
                 assert_eq!(term, $expected);
             } else {
                 panic!();
@@ -100,6 +301,67 @@ mod test {
         };
     }
 
+    macro_rules! mode {
+        ($mode:expr, $text:literal, $expected:literal) => {
+            if let Ok(PipelineResult::Term(mut term)) = pipeline::pipeline(Pipeline::Define, $text)
+            {
+                let options = Options {
+                    eta: false,
+                    mode: $mode,
+                    limit: None,
+                };
+                eval_with(&mut term, &options).unwrap();
+                match pipeline::pipeline(Pipeline::Define, $expected) {
+                    Ok(PipelineResult::Term(expected)) => {
+                        assert!(debruijn::alpha_equivalent(&term, &expected), "{}", term)
+                    }
+                    _ => panic!(),
+                }
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    macro_rules! diverges {
+        ($text:literal, $expected:literal) => {
+            if let Err(err) = pipeline::pipeline(Pipeline::Eval, $text) {
+                assert_eq!(err, $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    macro_rules! equivalent {
+        ($text1:literal, $text2:literal, $expected:expr) => {
+            match (
+                pipeline::pipeline(Pipeline::Define, $text1),
+                pipeline::pipeline(Pipeline::Define, $text2),
+            ) {
+                (Ok(PipelineResult::Term(term1)), Ok(PipelineResult::Term(term2))) => {
+                    assert_eq!(equivalent(&term1, &term2), Ok($expected));
+                }
+                _ => panic!(),
+            }
+        };
+    }
+
+    macro_rules! stats {
+        ($text:literal, $steps:expr, $size:expr, $depth:expr, $substitutions:expr) => {
+            if let Ok(PipelineResult::Term(mut term)) = pipeline::pipeline(Pipeline::Define, $text)
+            {
+                let stats = eval_stats(&mut term).unwrap();
+                assert_eq!(stats.beta_steps, $steps);
+                assert_eq!(stats.max_size, $size);
+                assert_eq!(stats.max_depth, $depth);
+                assert_eq!(stats.substitutions, $substitutions);
+            } else {
+                panic!();
+            }
+        };
+    }
+
     #[test]
     fn test() {
         run!("x", var_id('x', 0));
@@ -133,12 +395,166 @@ mod test {
             "(((x -> (y -> x)) (a -> a)) ((x -> (x x)) (x -> (x x))))",
             lambda_id('a', 2, var_id('a', 2))
         );
+        run!(
+            "(((x -> (x x)) (x -> (y -> (x y)))) z)",
+            lambda_id('y', 5, app(var_id('z', 3), var_id('y', 5)))
+        );
+        run!(
+            "((s -> (s s)) (a -> (b -> (a b))))",
+            lambda_id(
+                'b',
+                2,
+                lambda_id('b', 4, app(var_id('b', 2), var_id('b', 4)))
+            )
+        );
+        run!("(let x = y in (x x))", app(var_id('y', 1), var_id('y', 1)));
+        run!("((f x) where f = (y -> y))", var_id('x', 2));
+        run!(
+            "(let x = a in (let y = b in (x y)))",
+            app(var_id('a', 1), var_id('b', 3))
+        );
+        run!(
+            "(let rec f = (n -> ((n (m -> (f m))) z)) in (f (s -> (t -> (s (s -> (t -> (s (s -> (t -> t))))))))))",
+            var_id('z', 7)
+        );
         recursive!(
             "((x -> (x x)) (x -> (x x)))",
             app(
                 lambda_id('x', 1, app(var_id('x', 1), var_id('x', 1))),
-                lambda_id('x', 1, app(var_id('x', 1), var_id('x', 1)))
+                lambda_id('x', 2, app(var_id('x', 2), var_id('x', 2)))
             )
         );
     }
+
+    #[test]
+    fn measure() {
+        stats!("x", 0, 1, 1, 0);
+        stats!("(x -> x)", 0, 2, 2, 0);
+        stats!("((x -> x) y)", 1, 4, 3, 1);
+        stats!("((x -> y) z)", 1, 4, 3, 0);
+        stats!("((x -> (x x)) ((y -> y) (z -> z)))", 4, 11, 4, 5);
+    }
+
+    #[test]
+    fn divergence() {
+        diverges!("((x -> (x x)) (x -> (x x)))", "diverges: cycle of length 1");
+        diverges!(
+            "((x -> ((y -> (x x)) z)) (x -> ((y -> (x x)) z)))",
+            "diverges: cycle of length 2"
+        );
+        diverges!(
+            "(f ((x -> (x x)) (x -> (x x))))",
+            "diverges: cycle of length 1"
+        );
+    }
+
+    #[test]
+    fn eta() {
+        if let Ok(PipelineResult::Term(mut term)) =
+            pipeline::pipeline(Pipeline::Define, "((x -> (y -> (x y))) f)")
+        {
+            let options = Options {
+                eta: true,
+                mode: Mode::Normal,
+                limit: None,
+            };
+            let stats = eval_with(&mut term, &options).unwrap();
+            assert_eq!(term, var_id('f', 2));
+            assert_eq!(stats.beta_steps, 1);
+            assert_eq!(stats.eta_steps, 1);
+        } else {
+            panic!();
+        }
+        if let Ok(PipelineResult::Term(mut term)) =
+            pipeline::pipeline(Pipeline::Define, "(x -> (y -> ((f x) y)))")
+        {
+            let options = Options {
+                eta: true,
+                ..Options::default()
+            };
+            let mut steps = Vec::new();
+            let stats = trace(&mut term, &options, &mut |step| {
+                steps.push(step.to_string())
+            })
+            .unwrap();
+            assert_eq!(steps, vec!["(x -> (y -> ((f x) y)))", "(x -> (f x))", "f"]);
+            assert_eq!(stats.beta_steps, 0);
+            assert_eq!(stats.eta_steps, 2);
+        } else {
+            panic!();
+        }
+        equivalent!("(x -> (f x))", "f", true);
+        equivalent!("(x -> (y -> x))", "(a -> (b -> a))", true);
+        equivalent!("(x -> (y -> x))", "(x -> (y -> y))", false);
+        equivalent!(
+            "(((m -> (n -> (f -> (x -> ((m f) ((n f) x)))))) (f -> (x -> (f x)))) (f -> (x -> (f x))))",
+            "(f -> (x -> (f (f x))))",
+            true
+        );
+        equivalent!("(f -> (x -> (f x)))", "(f -> f)", true);
+    }
+
+    #[test]
+    fn tracing() {
+        if let Ok(PipelineResult::Term(mut term)) =
+            pipeline::pipeline(Pipeline::Define, "((x -> (f x)) ((y -> y) z))")
+        {
+            let mut steps = Vec::new();
+            trace(&mut term, &Options::default(), &mut |term| {
+                steps.push(format!("{}", term))
+            })
+            .unwrap();
+            assert_eq!(
+                steps,
+                vec!["((x -> (f x)) ((y -> y) z))", "(f ((y -> y) z))", "(f z)"]
+            );
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn stuck_function() {
+        // A function that can't reduce any further doesn't stop its argument
+        // from reducing.
+        run!(
+            "((f x) ((y -> y) z))",
+            app(app(var_id('f', 0), var_id('x', 1)), var_id('z', 3))
+        );
+        run!(
+            "(a -> ((f a) ((y -> y) a)))",
+            lambda_id(
+                'a',
+                0,
+                app(app(var_id('f', 1), var_id('a', 0)), var_id('a', 0))
+            )
+        );
+    }
+
+    #[test]
+    fn modes() {
+        mode!(Mode::WeakHead, "(x -> ((y -> y) x))", "(x -> ((y -> y) x))");
+        mode!(Mode::Head, "(x -> ((y -> y) x))", "(x -> x)");
+        mode!(Mode::Normal, "(x -> ((y -> y) x))", "(x -> x)");
+        mode!(Mode::WeakHead, "(f ((y -> y) x))", "(f ((y -> y) x))");
+        mode!(
+            Mode::Head,
+            "(x -> (f ((y -> y) x)))",
+            "(x -> (f ((y -> y) x)))"
+        );
+        mode!(Mode::Normal, "(x -> (f ((y -> y) x)))", "(x -> (f x))");
+        mode!(Mode::WeakHead, "(((x -> (y -> x)) (a -> a)) b)", "(a -> a)");
+
+        // (Y (s -> (cons a s))) = (p -> ((p a) (Y (s -> (cons a s)))))
+        mode!(
+            Mode::WeakHead,
+            "((f -> ((x -> (f (x x))) (x -> (f (x x))))) (s -> (((h -> (t -> (p -> ((p h) t)))) a) s)))",
+            "(p -> ((p a) ((x -> ((s -> (((h -> (t -> (p -> ((p h) t)))) a) s)) (x x))) (x -> ((s -> (((h -> (t -> (p -> ((p h) t)))) a) s)) (x x))))))"
+        );
+        mode!(
+            Mode::Head,
+            "((f -> ((x -> (f (x x))) (x -> (f (x x))))) (s -> (((h -> (t -> (p -> ((p h) t)))) a) s)))",
+            "(p -> ((p a) ((x -> ((s -> (((h -> (t -> (p -> ((p h) t)))) a) s)) (x x))) (x -> ((s -> (((h -> (t -> (p -> ((p h) t)))) a) s)) (x x))))))"
+        );
+    }
 }
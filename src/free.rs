@@ -0,0 +1,97 @@
+use crate::construct::Term;
+
+/// Free occurrences as their preorder index and name.
+pub fn free_vars(term: &Term) -> Vec<(usize, char)> {
+    let mut free = Vec::new();
+    do_free_vars(term, &mut Vec::new(), &mut 0, &mut free);
+    free
+}
+
+/// Distinct names of the free variables of `term` in alphabetical order.
+pub fn free_names(term: &Term) -> Vec<char> {
+    let mut names: Vec<char> = free_vars(term).into_iter().map(|(_, name)| name).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+fn do_free_vars(
+    term: &Term,
+    bound: &mut Vec<(char, Option<usize>)>,
+    index: &mut usize,
+    free: &mut Vec<(usize, char)>,
+) {
+    let current = *index;
+    *index += 1;
+    match term {
+        Term::Var(name, id) => {
+            let is_bound = bound
+                .iter()
+                .any(|(binder, binder_id)| match (binder_id, id) {
+                    (Some(binder_id), Some(id)) => binder_id == id,
+                    _ => binder == name,
+                });
+            if !is_bound {
+                free.push((current, *name));
+            }
+        }
+        Term::Lambda(name, id, _, body) | Term::Def(name, id, body) => {
+            bound.push((*name, *id));
+            do_free_vars(body, bound, index, free);
+            bound.pop();
+        }
+        Term::App(func, arg) => {
+            do_free_vars(func, bound, index, free);
+            do_free_vars(arg, bound, index, free);
+        }
+        Term::Let(name, id, _, value, body) => {
+            do_free_vars(value, bound, index, free);
+            bound.push((*name, *id));
+            do_free_vars(body, bound, index, free);
+            bound.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline;
+
+    macro_rules! free {
+        ($text:literal, $expected:expr) => {
+            let (term, _) = pipeline::define_located($text).unwrap();
+            assert_eq!(free_vars(&term), $expected);
+        };
+    }
+
+    #[test]
+    fn test() {
+        free!("(x -> x)", vec![]);
+        free!("(x -> y)", vec![(1, 'y')]);
+        free!("((x -> x) x)", vec![(3, 'x')]);
+        free!("(f : (x -> (f (g x))))", vec![(5, 'g')]);
+        free!(
+            "((x -> (y -> z)) (z y))",
+            vec![(3, 'z'), (5, 'z'), (6, 'y')]
+        );
+
+        let (term, _) = pipeline::define_located("((y x) (x -> (a y)))").unwrap();
+        assert_eq!(free_names(&term), vec!['a', 'x', 'y']);
+    }
+
+    #[test]
+    fn strict() {
+        assert_eq!(pipeline::check_closed("(x -> x)"), Ok(()));
+        assert_eq!(
+            pipeline::check_closed("(x -> y)"),
+            Err(String::from("1:7: unbound variable `y`"))
+        );
+        assert_eq!(
+            pipeline::check_closed("((x -> z)\n (f x))"),
+            Err(String::from(
+                "1:8: unbound variable `z`\n2:3: unbound variable `f`\n2:5: unbound variable `x`"
+            ))
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! Entry points for the fuzz targets in `fuzz/`, which must never panic.
+
+use crate::binary;
+use crate::construct;
+use crate::define;
+use crate::eval;
+use crate::json;
+use crate::json::Json;
+use crate::lex;
+use crate::lint;
+use crate::lsp;
+use crate::parse;
+use crate::pipeline;
+use crate::pipeline::Pipeline;
+use crate::ski;
+use crate::systemf;
+
+/// Most beta steps taken when evaluating fuzzed programs.
+pub const STEP_LIMIT: usize = 1000;
+
+/// Most positions queried in a fuzzed document, each of which reanalyses it.
+pub const POSITION_LIMIT: usize = 20;
+
+pub fn lex(data: &[u8]) {
+    if let Ok(input) = std::str::from_utf8(data) {
+        let _ = lex::lex_spanned(input);
+    }
+}
+
+pub fn parse(data: &[u8]) {
+    let tokens = match std::str::from_utf8(data).map(lex::lex_spanned) {
+        Ok(Ok(tokens)) => tokens,
+        _ => return,
+    };
+    let bare: Vec<_> = tokens.iter().map(|(token, _)| *token).collect();
+    let _ = parse::parse(&bare);
+    let _ = parse::spans(&tokens);
+    let _ = parse::parse_recovering(&tokens);
+}
+
+pub fn construct(data: &[u8]) {
+    let tokens = match std::str::from_utf8(data).map(lex::lex_spanned) {
+        Ok(Ok(tokens)) => tokens,
+        _ => return,
+    };
+    let bare: Vec<_> = tokens.iter().map(|(token, _)| *token).collect();
+    if let Ok(tree) = parse::parse(&bare) {
+        let _ = construct::construct_located(&tree, &parse::spans(&tokens));
+    }
+    for (tree, spans) in parse::parse_recovering(&tokens).0 {
+        let _ = construct::construct_recovering(&tree, &spans);
+    }
+}
+
+/// Runs every stage and prints the result.
+pub fn pipeline(data: &[u8]) {
+    let input = match std::str::from_utf8(data) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+    let _ = pipeline::recover(input);
+    let _ = pipeline::lint(input, &lint::Options::default());
+    let _ = pipeline::format(input, 80);
+    let _ = pipeline::infer(input);
+    let mut term = match pipeline::term(Pipeline::Typecheck, input) {
+        Ok(term) => term,
+        Err(_) => return,
+    };
+    let options = eval::Options {
+        limit: Some(STEP_LIMIT),
+        ..eval::Options::default()
+    };
+    if eval::eval_with(&mut term, &options).is_ok() {
+        let _ = term.to_string();
+    }
+}
+
+pub fn decode(data: &[u8]) {
+    if let Ok(Ok(term)) = std::str::from_utf8(data).map(binary::decode) {
+        let _ = binary::encode(&term);
+        let _ = term.to_string();
+    }
+}
+
+/// Checks a System F program, then evaluates its erasure.
+pub fn systemf(data: &[u8]) {
+    let tree = match std::str::from_utf8(data).map(lex::lex) {
+        Ok(Ok(tokens)) => match parse::parse(&tokens) {
+            Ok(tree) => tree,
+            Err(_) => return,
+        },
+        _ => return,
+    };
+    let typed = match systemf::construct(&tree) {
+        Ok(typed) => typed,
+        Err(_) => return,
+    };
+    let _ = typed.to_string();
+    if let Ok(ty) = systemf::typecheck(&typed) {
+        let _ = ty.to_string();
+        let mut term = systemf::erase(&typed);
+        define::define(&mut term);
+        let options = eval::Options {
+            limit: Some(STEP_LIMIT),
+            ..eval::Options::default()
+        };
+        let _ = eval::eval_with(&mut term, &options);
+    }
+}
+
+pub fn ski(data: &[u8]) {
+    let term =
+        match std::str::from_utf8(data).map(|input| pipeline::term(Pipeline::Typecheck, input)) {
+            Ok(Ok(term)) => term,
+            _ => return,
+        };
+    for bc in &[false, true] {
+        if let Ok(combinators) = ski::translate(&term, &ski::Options { bc: *bc }) {
+            if let Ok((term, _)) = ski::reduce(&combinators) {
+                let _ = term.to_string();
+            }
+        }
+    }
+}
+
+/// Serves `data` as messages, then queries it as a document.
+pub fn lsp(data: &[u8]) {
+    const URI: &str = "file:///fuzz.lam";
+    let _ = lsp::serve(&mut &data[..], &mut std::io::sink());
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let mut server = lsp::Server::default();
+    server.handle(&json::object(vec![
+        ("method", json::string("textDocument/didOpen")),
+        (
+            "params",
+            json::object(vec![(
+                "textDocument",
+                json::object(vec![
+                    ("uri", json::string(URI)),
+                    ("text", json::string(text)),
+                ]),
+            )]),
+        ),
+    ]));
+    let positions = text.split('\n').enumerate().flat_map(|(line, content)| {
+        (0..=content.chars().count()).map(move |character| (line, character))
+    });
+    for (line, character) in positions.take(POSITION_LIMIT) {
+        for method in &["hover", "definition", "rename"] {
+            let position = json::object(vec![
+                ("line", Json::Number(line as f64)),
+                ("character", Json::Number(character as f64)),
+            ]);
+            server.handle(&json::object(vec![
+                ("id", Json::Number(1.0)),
+                ("method", json::string(&format!("textDocument/{}", method))),
+                (
+                    "params",
+                    json::object(vec![
+                        (
+                            "textDocument",
+                            json::object(vec![("uri", json::string(URI))]),
+                        ),
+                        ("position", position),
+                        ("newName", json::string("z")),
+                    ]),
+                ),
+            ]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::random::Rng;
+
+    const BYTES: &[u8] = b"()[]->:=xyaf01 assert\n";
+
+    fn all(data: &[u8]) {
+        lex(data);
+        parse(data);
+        construct(data);
+        pipeline(data);
+        decode(data);
+        systemf(data);
+        ski(data);
+        lsp(data);
+    }
+
+    #[test]
+    fn test() {
+        for input in &[
+            "(x -> ->)",
+            "(x -> :)",
+            "(x -> (f : x))",
+            "(f : (x -> (g : g)))",
+            "(assert (x -> x) == x)",
+            "((x -> (x x)) (x -> (x x)))",
+            "(x [a -> ] -> x)",
+            "x ] (",
+            "(let rec f = (x -> f) in f)",
+            "(x where = in let rec)",
+            "\u{0}(\u{10ffff}",
+            "0010",
+            "000010110",
+            "([a] -> ([a] -> (x [a] -> (x [[a] -> a]))))",
+            "(((x [[a] -> a] -> x) [a]) y)",
+            "(x -> (y -> (x y)))",
+            "Content-Length: 2\r\n\r\n{}",
+            "Content-Length: 18446744073709551615\r\n\r\n",
+            "Content-Length: 40\r\n\r\n{\"method\":\"textDocument/hover\",\"id\":1}",
+        ] {
+            all(input.as_bytes());
+        }
+        all(&[0xff, b'(', b'x']);
+        all("(".repeat(100_000).as_bytes());
+        all(format!("{}x{}", "(x -> ".repeat(10_000), ")".repeat(10_000)).as_bytes());
+        let limit = parse::NESTING_LIMIT;
+        all(format!("{}x{}", "(x -> ".repeat(limit), ")".repeat(limit)).as_bytes());
+        all(format!("{}x{}", "(f ".repeat(limit), ")".repeat(limit)).as_bytes());
+        all(format!(
+            "({}x{} y)",
+            "(x -> ".repeat(limit - 1),
+            ")".repeat(limit - 1)
+        )
+        .as_bytes());
+        all(format!("(x {})", "x ".repeat(100_000)).as_bytes());
+        all("00".repeat(100_000).as_bytes());
+        let letters: String = ('a'..='z').map(|name| format!("([{}] -> ", name)).collect();
+        all(format!("{}([a] -> (x [a] -> x)){}", letters, ")".repeat(26)).as_bytes());
+    }
+
+    #[test]
+    fn random() {
+        let mut rng = Rng::new(0);
+        for _ in 0..2000 {
+            let length = rng.below(40);
+            let data: Vec<u8> = (0..length).map(|_| BYTES[rng.below(BYTES.len())]).collect();
+            all(&data);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Golden-file tests comparing each `name.lam` with `name.expected`.
+
+use crate::check;
+use crate::check::Report;
+use crate::pipeline;
+use crate::pipeline::Pipeline;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Passed,
+    Failed { expected: String, actual: String },
+    Missing { actual: String },
+    Blessed,
+    Checked(Report),
+}
+
+impl Status {
+    pub fn passed(&self) -> bool {
+        match self {
+            Status::Passed | Status::Blessed => true,
+            Status::Failed { .. } | Status::Missing { .. } => false,
+            Status::Checked(report) => report.failures.is_empty(),
+        }
+    }
+}
+
+/// What running a program prints.
+pub fn output(input: &str) -> String {
+    match pipeline::term(Pipeline::Eval, input) {
+        Ok(term) => format!("{}\n", term),
+        Err(err) => format!("{}\n", err),
+    }
+}
+
+/// Runs `path`, or every `.lam` file in it, overwriting with `bless`.
+pub fn run(path: &Path, bless: bool) -> Result<Vec<(PathBuf, Status)>, String> {
+    if path.is_file() {
+        return Ok(vec![(path.to_path_buf(), run_program(path, bless)?)]);
+    }
+    let entries = fs::read_dir(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let mut programs = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|err| err.to_string())?.path();
+        if path.extension().is_some_and(|extension| extension == "lam") {
+            programs.push(path);
+        }
+    }
+    programs.sort();
+
+    let mut results = Vec::new();
+    for program in programs {
+        let status = run_program(&program, bless)?;
+        results.push((program, status));
+    }
+    Ok(results)
+}
+
+fn run_program(program: &Path, bless: bool) -> Result<Status, String> {
+    let input =
+        fs::read_to_string(program).map_err(|err| format!("{}: {}", program.display(), err))?;
+    if let Ok(Some(report)) = check::check(&input) {
+        return Ok(Status::Checked(report));
+    }
+    let actual = output(&input);
+    let expected_path = program.with_extension("expected");
+    Ok(match fs::read_to_string(&expected_path) {
+        Ok(expected) if expected == actual => Status::Passed,
+        _ if bless => {
+            fs::write(&expected_path, &actual)
+                .map_err(|err| format!("{}: {}", expected_path.display(), err))?;
+            Status::Blessed
+        }
+        Ok(expected) => Status::Failed { expected, actual },
+        Err(_) => Status::Missing { actual },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Runs the programs in `tests/golden`. Set `BLESS=1` to update them.
+    #[test]
+    fn golden() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        let bless = std::env::var_os("BLESS").is_some();
+        let results = run(&dir, bless).unwrap();
+        assert!(!results.is_empty());
+        for (program, status) in results {
+            match status {
+                Status::Passed | Status::Blessed => (),
+                Status::Checked(report) => assert!(
+                    report.failures.is_empty(),
+                    "{}: {}",
+                    program.display(),
+                    report.failures.join("\n")
+                ),
+                Status::Failed { expected, actual } => panic!(
+                    "{}: expected {:?}, found {:?}",
+                    program.display(),
+                    expected,
+                    actual
+                ),
+                Status::Missing { actual } => panic!(
+                    "{}: missing expected output, found {:?}",
+                    program.display(),
+                    actual
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn formatted() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "lam") {
+                let text = fs::read_to_string(&path).unwrap();
+                let formatted = pipeline::format(&text, 80);
+                assert_eq!(formatted, Ok(text), "{}", path.display());
+            }
+        }
+    }
+
+    #[test]
+    fn bless() {
+        let dir = std::env::temp_dir().join(format!("lambda-golden-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("id.lam"), "((x -> x) y)\n").unwrap();
+        fs::write(dir.join("bad.lam"), "(x -> y z)").unwrap();
+        fs::write(dir.join("bad.expected"), "(x -> y)\n").unwrap();
+        fs::write(dir.join("notes.txt"), "not a program").unwrap();
+
+        let results = run(&dir, false).unwrap();
+        assert_eq!(
+            results,
+            vec![
+                (
+                    dir.join("bad.lam"),
+                    Status::Failed {
+                        expected: String::from("(x -> y)\n"),
+                        actual: String::from("Lambda body has too many terms\n"),
+                    }
+                ),
+                (
+                    dir.join("id.lam"),
+                    Status::Missing {
+                        actual: String::from("y\n")
+                    }
+                ),
+            ]
+        );
+
+        let results = run(&dir, true).unwrap();
+        assert_eq!(results[0].1, Status::Blessed);
+        assert_eq!(results[1].1, Status::Blessed);
+        assert_eq!(fs::read_to_string(dir.join("id.expected")).unwrap(), "y\n");
+        let results = run(&dir, false).unwrap();
+        assert_eq!(results[0].1, Status::Passed);
+        assert_eq!(results[1].1, Status::Passed);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
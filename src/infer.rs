@@ -0,0 +1,394 @@
+use crate::construct;
+use crate::construct::Term;
+use crate::typecheck;
+use crate::typecheck::TypeError;
+use crate::types;
+use crate::types::Type;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// Monotype during inference. `Var` is a unification variable.
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Var(usize),
+    Con(char),
+    Arrow(Box<Ty>, Box<Ty>),
+}
+
+#[derive(Debug, Clone)]
+struct Scheme {
+    vars: Vec<usize>,
+    ty: Ty,
+}
+
+type Subst = HashMap<usize, Ty>;
+
+type Env = HashMap<usize, Scheme>;
+
+/// Infers the principal type of `term` with Algorithm W.
+pub fn infer(term: &Term) -> Result<Type, TypeError> {
+    let mut inferer = Inferer { next: 0, index: 0 };
+    let (_, ty) = inferer.w(&Env::new(), term)?;
+    Ok(readable(&[&ty]).map_err(|err| (0, err))?.remove(0))
+}
+
+struct Inferer {
+    next: usize,
+    index: usize,
+}
+
+impl Inferer {
+    fn fresh(&mut self) -> Ty {
+        self.next += 1;
+        Ty::Var(self.next - 1)
+    }
+
+    fn visit(&mut self) -> usize {
+        self.index += 1;
+        self.index - 1
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Ty {
+        let subst = scheme.vars.iter().map(|var| (*var, self.fresh())).collect();
+        apply(&subst, &scheme.ty)
+    }
+
+    fn w(&mut self, env: &Env, term: &Term) -> Result<(Subst, Ty), TypeError> {
+        let index = self.visit();
+        match term {
+            Term::Var(name, id) => match env.get(&typecheck::defined(index, *id)?) {
+                Some(scheme) => Ok((Subst::new(), self.instantiate(scheme))),
+                None => Err((index, format!("Unbound variable `{}`", name))),
+            },
+            Term::Lambda(_, id, ty, body) => {
+                let param = match ty {
+                    Some(ty) => from_type(ty).map_err(|err| (index, err))?,
+                    None => self.fresh(),
+                };
+                let mut env = env.clone();
+                env.insert(typecheck::defined(index, *id)?, mono(param.clone()));
+                let (subst, result) = self.w(&env, body)?;
+                let param = apply(&subst, &param);
+                Ok((subst, Ty::Arrow(Box::new(param), Box::new(result))))
+            }
+            Term::App(func, arg) => {
+                let (subst1, func) = self.w(env, func)?;
+                let (subst2, arg) = self.w(&apply_env(&subst1, env), arg)?;
+                let result = self.fresh();
+                let expected = Ty::Arrow(Box::new(arg), Box::new(result.clone()));
+                let subst3 =
+                    unify(&apply(&subst2, &func), &expected).map_err(|err| (index, err))?;
+                let subst = compose(&subst3, &compose(&subst2, &subst1));
+                Ok((subst, apply(&subst3, &result)))
+            }
+            Term::Def(_, id, body) => {
+                let ty = self.fresh();
+                let mut inner = env.clone();
+                inner.insert(typecheck::defined(index, *id)?, mono(ty.clone()));
+                let (subst1, body) = self.w(&inner, body)?;
+                let subst2 = unify(&apply(&subst1, &ty), &body).map_err(|err| (index, err))?;
+                let subst = compose(&subst2, &subst1);
+                let scheme = generalize(&apply_env(&subst, env), &apply(&subst2, &body));
+                Ok((subst, self.instantiate(&scheme)))
+            }
+            Term::Let(_, id, rec, value, body) => {
+                let fixed = if *rec { recursive(value) } else { None };
+                let (subst1, value) = match fixed {
+                    Some((fixpoint, binder, value)) => self.fix(env, fixpoint, binder, value)?,
+                    None => self.w(env, value)?,
+                };
+                let env = apply_env(&subst1, env);
+                let mut inner = env.clone();
+                inner.insert(typecheck::defined(index, *id)?, generalize(&env, &value));
+                let (subst2, body) = self.w(&inner, body)?;
+                Ok((compose(&subst2, &subst1), body))
+            }
+        }
+    }
+
+    /// Infers the value of a `let rec` with `f` of its own type.
+    fn fix(
+        &mut self,
+        env: &Env,
+        fixpoint: &Term,
+        binder: Option<usize>,
+        value: &Term,
+    ) -> Result<(Subst, Ty), TypeError> {
+        self.index += 1 + construct::size(fixpoint);
+        let index = self.visit();
+        let ty = self.fresh();
+        let mut inner = env.clone();
+        inner.insert(typecheck::defined(index, binder)?, mono(ty.clone()));
+        let (subst1, value) = self.w(&inner, value)?;
+        let subst2 = unify(&apply(&subst1, &ty), &value).map_err(|err| (index, err))?;
+        Ok((compose(&subst2, &subst1), apply(&subst2, &value)))
+    }
+}
+
+/// The fixpoint, binder and body of the value `(Y (f -> value))` of a `let rec`.
+fn recursive(term: &Term) -> Option<(&Term, Option<usize>, &Term)> {
+    match term {
+        Term::App(fixpoint, arg) => match &**arg {
+            Term::Lambda(_, id, None, body) => Some((fixpoint, *id, body)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn mono(ty: Ty) -> Scheme {
+    Scheme {
+        vars: Vec::new(),
+        ty,
+    }
+}
+
+fn from_type(ty: &Type) -> Result<Ty, String> {
+    match ty {
+        Type::Var(name) => Ok(Ty::Con(*name)),
+        Type::Arrow(param, result) => Ok(Ty::Arrow(
+            Box::new(from_type(param)?),
+            Box::new(from_type(result)?),
+        )),
+        Type::Forall(..) => Err(format!("Cannot infer with polymorphic annotation `{}`", ty)),
+    }
+}
+
+fn apply(subst: &Subst, ty: &Ty) -> Ty {
+    match ty {
+        Ty::Var(var) => match subst.get(var) {
+            Some(ty) => ty.clone(),
+            None => ty.clone(),
+        },
+        Ty::Con(_) => ty.clone(),
+        Ty::Arrow(param, result) => Ty::Arrow(
+            Box::new(apply(subst, param)),
+            Box::new(apply(subst, result)),
+        ),
+    }
+}
+
+fn apply_scheme(subst: &Subst, scheme: &Scheme) -> Scheme {
+    let subst = subst
+        .iter()
+        .filter(|(var, _)| !scheme.vars.contains(var))
+        .map(|(var, ty)| (*var, ty.clone()))
+        .collect();
+    Scheme {
+        vars: scheme.vars.clone(),
+        ty: apply(&subst, &scheme.ty),
+    }
+}
+
+fn apply_env(subst: &Subst, env: &Env) -> Env {
+    env.iter()
+        .map(|(id, scheme)| (*id, apply_scheme(subst, scheme)))
+        .collect()
+}
+
+/// Substitution applying `subst2` and then `subst1`.
+fn compose(subst1: &Subst, subst2: &Subst) -> Subst {
+    let mut subst: Subst = subst2
+        .iter()
+        .map(|(var, ty)| (*var, apply(subst1, ty)))
+        .collect();
+    for (var, ty) in subst1 {
+        subst.entry(*var).or_insert_with(|| ty.clone());
+    }
+    subst
+}
+
+fn free_vars(ty: &Ty, vars: &mut HashSet<usize>) {
+    match ty {
+        Ty::Var(var) => {
+            vars.insert(*var);
+        }
+        Ty::Con(_) => (),
+        Ty::Arrow(param, result) => {
+            free_vars(param, vars);
+            free_vars(result, vars);
+        }
+    }
+}
+
+fn generalize(env: &Env, ty: &Ty) -> Scheme {
+    let mut bound = HashSet::new();
+    for scheme in env.values() {
+        let mut vars = HashSet::new();
+        free_vars(&scheme.ty, &mut vars);
+        bound.extend(vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+    }
+    let mut vars = HashSet::new();
+    free_vars(ty, &mut vars);
+    let mut vars: Vec<usize> = vars.difference(&bound).copied().collect();
+    vars.sort_unstable();
+    Scheme {
+        vars,
+        ty: ty.clone(),
+    }
+}
+
+fn unify(ty1: &Ty, ty2: &Ty) -> Result<Subst, String> {
+    match (ty1, ty2) {
+        (Ty::Var(var1), Ty::Var(var2)) if var1 == var2 => Ok(Subst::new()),
+        (Ty::Var(var), ty) | (ty, Ty::Var(var)) => {
+            let mut vars = HashSet::new();
+            free_vars(ty, &mut vars);
+            if vars.contains(var) {
+                let names = readable(&[&Ty::Var(*var), ty])?;
+                Err(format!(
+                    "Cannot construct infinite type `{} = {}`",
+                    names[0], names[1]
+                ))
+            } else {
+                let mut subst = Subst::new();
+                subst.insert(*var, ty.clone());
+                Ok(subst)
+            }
+        }
+        (Ty::Con(name1), Ty::Con(name2)) if name1 == name2 => Ok(Subst::new()),
+        (Ty::Arrow(param1, result1), Ty::Arrow(param2, result2)) => {
+            let subst1 = unify(param1, param2)?;
+            let subst2 = unify(&apply(&subst1, result1), &apply(&subst1, result2))?;
+            Ok(compose(&subst2, &subst1))
+        }
+        _ => {
+            let names = readable(&[ty1, ty2])?;
+            Err(format!("Cannot unify `{}` with `{}`", names[0], names[1]))
+        }
+    }
+}
+
+/// Names unification variables with letters unused by annotations.
+fn readable(tys: &[&Ty]) -> Result<Vec<Type>, String> {
+    fn constants(ty: &Ty, used: &mut HashSet<char>) {
+        match ty {
+            Ty::Var(_) => (),
+            Ty::Con(name) => {
+                used.insert(*name);
+            }
+            Ty::Arrow(param, result) => {
+                constants(param, used);
+                constants(result, used);
+            }
+        }
+    }
+
+    fn convert(
+        ty: &Ty,
+        names: &mut HashMap<usize, char>,
+        letters: &mut impl Iterator<Item = char>,
+    ) -> Result<Type, String> {
+        match ty {
+            Ty::Var(var) => {
+                let name = match names.get(var) {
+                    Some(name) => *name,
+                    None => {
+                        let name = letters
+                            .next()
+                            .ok_or_else(|| String::from("Ran out of type variable names"))?;
+                        names.insert(*var, name);
+                        name
+                    }
+                };
+                Ok(types::var(name))
+            }
+            Ty::Con(name) => Ok(types::var(*name)),
+            Ty::Arrow(param, result) => {
+                let param = convert(param, names, letters)?;
+                Ok(types::arrow(param, convert(result, names, letters)?))
+            }
+        }
+    }
+
+    let mut used = HashSet::new();
+    for ty in tys {
+        constants(ty, &mut used);
+    }
+    let mut letters = ('a'..='z').filter(|letter| !used.contains(letter));
+    let mut names = HashMap::new();
+    tys.iter()
+        .map(|ty| convert(ty, &mut names, &mut letters))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pipeline;
+
+    macro_rules! ok {
+        ($text:literal, $expected:literal) => {
+            match pipeline::infer($text) {
+                Ok(ty) => assert_eq!(format!("{}", ty), $expected),
+                Err(err) => panic!("{}", err),
+            }
+        };
+    }
+
+    macro_rules! err {
+        ($text:literal, $expected:literal) => {
+            if let Err(err) = pipeline::infer($text) {
+                assert_eq!(err, $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    #[test]
+    fn test() {
+        ok!("(x -> x)", "a -> a");
+        ok!("(x -> (y -> x))", "a -> b -> a");
+        ok!("(f -> (x -> (f x)))", "(a -> b) -> a -> b");
+        ok!(
+            "(f -> (g -> (x -> (f (g x)))))",
+            "(a -> b) -> (c -> a) -> c -> b"
+        );
+        ok!("(f -> (x -> (f (f x))))", "(a -> a) -> a -> a");
+        ok!(
+            "(n -> (f -> (x -> (f ((n f) x)))))",
+            "((a -> b) -> c -> a) -> (a -> b) -> c -> b"
+        );
+        ok!("(x -> (y -> ((x y) y)))", "(a -> a -> b) -> a -> b");
+        ok!("((x -> x) (y -> y))", "a -> a");
+        ok!("(x [b] -> (y -> x))", "b -> a -> b");
+        ok!("(f : (x -> (f x)))", "a -> b");
+        err!(
+            "(x [[a] -> a] -> x)",
+            "1:1: Cannot infer with polymorphic annotation `[a] -> a`"
+        );
+        err!(
+            "(x -> (x x))",
+            "1:7: Cannot construct infinite type `a = a -> b`"
+        );
+        err!("(x -> y)", "1:7: Unbound variable `y`");
+        err!(
+            "((x [a] -> x) (y [b] -> y))",
+            "1:1: Cannot unify `a` with `b -> b`"
+        );
+
+        ok!("(let i = (x -> x) in (i i))", "a -> a");
+        ok!("((i i) where i = (x -> x))", "a -> a");
+        ok!("(let k = (x -> (y -> x)) in ((k k) (k k)))", "a -> b -> a");
+        ok!("(let rec f = (x -> (f x)) in f)", "a -> b");
+        ok!("(let rec f = (x -> (y -> ((f y) x))) in f)", "a -> a -> b");
+        err!(
+            "((i -> (i i)) (x -> x))",
+            "1:8: Cannot construct infinite type `a = a -> b`"
+        );
+        err!(
+            "(let rec f = (x -> (f f)) in f)",
+            "1:20: Cannot construct infinite type `a = a -> b`"
+        );
+        err!(
+            "(let f = ((g -> ((x -> (g (x x))) (x -> (g (x x))))) (f -> (x -> (f x)))) in f)",
+            "1:27: Cannot construct infinite type `a = a -> b`"
+        );
+
+        let text = format!("{}x{}", "(x -> ".repeat(27), ")".repeat(27));
+        assert_eq!(
+            pipeline::infer(&text),
+            Err(String::from("1:1: Ran out of type variable names"))
+        );
+    }
+}
@@ -0,0 +1,243 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        fields
+            .into_iter()
+            .map(|(name, value)| (String::from(name), value))
+            .collect(),
+    )
+}
+
+pub fn string(value: &str) -> Json {
+    Json::String(String::from(value))
+}
+
+pub fn parse(input: &str) -> Result<Json, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut parser = Parser { chars, position: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position < parser.chars.len() {
+        return Err(format!(
+            "Unexpected '{}' after JSON value",
+            parser.chars[parser.position]
+        ));
+    }
+    Ok(value)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\n' | '\r' | '\t') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Result<char, String> {
+        let ch = self.peek();
+        self.position += 1;
+        ch.ok_or_else(|| String::from("Unexpected end of JSON"))
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.next()? {
+            ch if ch == expected => Ok(()),
+            ch => Err(format!("Expected '{}', found '{}'", expected, ch)),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some('-' | '0'..='9') => self.number(),
+            Some(ch) => Err(format!("Unexpected '{}' in JSON", ch)),
+            None => Err(String::from("Unexpected end of JSON")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while let Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9') = self.peek() {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| format!("Invalid number '{}'", text))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(string),
+                '\\' => match self.next()? {
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    'r' => string.push('\r'),
+                    'b' => string.push('\u{8}'),
+                    'f' => string.push('\u{c}'),
+                    'u' => {
+                        let mut code = 0;
+                        for _ in 0..4 {
+                            let digit = self.next()?;
+                            code = code * 16
+                                + digit
+                                    .to_digit(16)
+                                    .ok_or_else(|| format!("Invalid hex digit '{}'", digit))?;
+                        }
+                        string.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    ch => string.push(ch),
+                },
+                ch => string.push(ch),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => (),
+                ']' => return Ok(Json::Array(items)),
+                ch => return Err(format!("Expected ',' or ']', found '{}'", ch)),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let name = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            fields.push((name, self.value()?));
+            self.skip_whitespace();
+            match self.next()? {
+                ',' => (),
+                '}' => return Ok(Json::Object(fields)),
+                ch => return Err(format!("Expected ',' or '}}', found '{}'", ch)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let text = r#"{"a": [1, -2.5, true, null], "b": "x\"\nA"}"#;
+        let json = parse(text).unwrap();
+        assert_eq!(
+            json,
+            object(vec![
+                (
+                    "a",
+                    Json::Array(vec![
+                        Json::Number(1.0),
+                        Json::Number(-2.5),
+                        Json::Bool(true),
+                        Json::Null
+                    ])
+                ),
+                ("b", string("x\"\nA")),
+            ])
+        );
+        assert_eq!(
+            format!("{}", json),
+            r#"{"a":[1,-2.5,true,null],"b":"x\"\nA"}"#
+        );
+        assert_eq!(parse(&format!("{}", json)), Ok(json));
+        assert_eq!(parse("[1,]"), Err(String::from("Unexpected ']' in JSON")));
+        assert_eq!(
+            parse("{} x"),
+            Err(String::from("Unexpected 'x' after JSON value"))
+        );
+        assert_eq!(parse("\"a"), Err(String::from("Unexpected end of JSON")));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::source::Span;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Token {
     Name(char),
@@ -5,45 +7,211 @@ pub enum Token {
     Colon,
     Open,
     Close,
+    OpenBracket,
+    CloseBracket,
+    Assert,
+    Equals,
+    Let,
+    Rec,
+    In,
+    Where,
+    Bind,
+}
+
+/// Words lexed as a single token when the whole run of letters matches.
+const KEYWORDS: &[(&str, Token)] = &[
+    ("assert", Token::Assert),
+    ("let", Token::Let),
+    ("rec", Token::Rec),
+    ("in", Token::In),
+    ("where", Token::Where),
+];
+
+// How far a branch has got through a `let`, which decides whether `in` is a
+// keyword.
+#[derive(Clone, Copy, PartialEq)]
+enum Binding {
+    None,
+    Let,
+    Bound,
+    Value,
+}
+
+fn word(chars: &[char], start: usize) -> usize {
+    if start > 0 && chars[start - 1].is_ascii_lowercase() {
+        return 0;
+    }
+    chars[start..]
+        .iter()
+        .take_while(|ch| ch.is_ascii_lowercase())
+        .count()
+}
+
+fn skip_spaces(chars: &[char], start: usize) -> usize {
+    start
+        + chars[start..]
+            .iter()
+            .take_while(|ch| **ch == ' ' || **ch == '\n')
+            .count()
 }
 
-pub type TokenIter<'a> = std::iter::Peekable<std::slice::Iter<'a, Token>>;
+// Whether the text from `start` reads `[rec] x =`.
+fn binding_follows(chars: &[char], start: usize) -> bool {
+    let mut index = skip_spaces(chars, start);
+    if chars[index..].starts_with(&['r', 'e', 'c']) && word(chars, index) == 3 {
+        index = skip_spaces(chars, index + 3);
+    }
+    if index >= chars.len() || word(chars, index) != 1 {
+        return false;
+    }
+    index = skip_spaces(chars, index + 1);
+    chars.get(index) == Some(&'=') && chars.get(index + 1) != Some(&'=')
+}
+
+fn keyword(
+    chars: &[char],
+    start: usize,
+    previous: Option<Token>,
+    binding: Binding,
+) -> Option<(usize, Token)> {
+    let length = word(chars, start);
+    let word: String = chars[start..start + length].iter().collect();
+    let token = KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == word)
+        .map(|(_, token)| *token)?;
+    let end = start + length;
+    let allowed = match token {
+        Token::Let => matches!(previous, None | Some(Token::Open)) && binding_follows(chars, end),
+        Token::Rec => {
+            matches!(previous, Some(Token::Let) | Some(Token::Where)) && binding_follows(chars, end)
+        }
+        Token::Where => previous.is_some() && binding_follows(chars, end),
+        Token::In => binding == Binding::Value,
+        _ => true,
+    };
+    if allowed {
+        Some((length, token))
+    } else {
+        None
+    }
+}
+
+fn track(bindings: &mut Vec<Binding>, token: Token) {
+    let item = match token {
+        Token::Open => {
+            bindings.push(Binding::None);
+            false
+        }
+        Token::Close => {
+            if bindings.len() > 1 {
+                bindings.pop();
+            }
+            true
+        }
+        Token::Name(_) => true,
+        _ => false,
+    };
+    if let Some(binding) = bindings.last_mut() {
+        *binding = match (*binding, token) {
+            (_, Token::Let) => Binding::Let,
+            (_, Token::In) => Binding::None,
+            (Binding::Let, Token::Bind) => Binding::Bound,
+            (Binding::Bound, _) if item => Binding::Value,
+            (binding, _) => binding,
+        };
+    }
+}
 
 pub fn lex(input: &str) -> Result<Vec<Token>, String> {
+    Ok(lex_spanned(input)?
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect())
+}
+
+pub fn lex_spanned(input: &str) -> Result<Vec<(Token, Span)>, String> {
     enum State {
         Any,
         Arrow,
+        /// Skipping the rest of a token that is already pushed.
+        Skip(usize),
     }
 
+    let chars: Vec<char> = input.chars().collect();
     let mut tokens = Vec::new();
     let mut state = State::Any;
+    let mut bindings = vec![Binding::None];
 
-    for ch in input.chars() {
+    for (start, &ch) in chars.iter().enumerate() {
+        let pushed = tokens.len();
+        let span = Span {
+            start,
+            end: start + 1,
+        };
         match state {
             State::Any => match ch {
                 ' ' | '\n' => (),
-                'a'..='z' => tokens.push(Token::Name(ch)),
-                '(' => tokens.push(Token::Open),
-                ')' => tokens.push(Token::Close),
-                ':' => tokens.push(Token::Colon),
+                'a'..='z' => match keyword(
+                    &chars,
+                    start,
+                    tokens.last().map(|(token, _)| *token),
+                    *bindings.last().unwrap_or(&Binding::None),
+                ) {
+                    Some((length, token)) => {
+                        let span = Span {
+                            start,
+                            end: start + length,
+                        };
+                        tokens.push((token, span));
+                        state = State::Skip(length - 1);
+                    }
+                    None => tokens.push((Token::Name(ch), span)),
+                },
+                '(' => tokens.push((Token::Open, span)),
+                ')' => tokens.push((Token::Close, span)),
+                '[' => tokens.push((Token::OpenBracket, span)),
+                ']' => tokens.push((Token::CloseBracket, span)),
+                ':' => tokens.push((Token::Colon, span)),
                 '-' => state = State::Arrow,
+                '=' if chars.get(start + 1) == Some(&'=') => {
+                    let span = Span {
+                        start,
+                        end: start + 2,
+                    };
+                    tokens.push((Token::Equals, span));
+                    state = State::Skip(1);
+                }
+                '=' => tokens.push((Token::Bind, span)),
                 '>' => return Err(String::from("'>' must be preceded by '-'")),
                 _ => return Err(format!("'{}' is never a valid character", ch)),
             },
             State::Arrow => match ch {
                 '>' => {
-                    tokens.push(Token::Arrow);
+                    let span = Span {
+                        start: start - 1,
+                        end: start + 1,
+                    };
+                    tokens.push((Token::Arrow, span));
                     state = State::Any;
                 }
                 _ => return Err(String::from("'-' must be followed by '>'")),
             },
+            State::Skip(remaining) => {
+                state = match remaining {
+                    1 => State::Any,
+                    _ => State::Skip(remaining - 1),
+                }
+            }
+        }
+        if let Some((token, _)) = tokens.get(pushed) {
+            track(&mut bindings, *token);
         }
     }
 
-    if let State::Arrow = state {
-        Err(String::from("'-' must be followed by '>'"))
-    } else {
-        Ok(tokens)
+    match state {
+        State::Arrow => Err(String::from("'-' must be followed by '>'")),
+        State::Any | State::Skip(_) => Ok(tokens),
     }
 }
 
@@ -120,9 +288,164 @@ mod test {
                 Token::Close
             ]
         );
+        ok!(
+            "(x [a] -> x)",
+            vec![
+                Token::Open,
+                Token::Name('x'),
+                Token::OpenBracket,
+                Token::Name('a'),
+                Token::CloseBracket,
+                Token::Arrow,
+                Token::Name('x'),
+                Token::Close
+            ]
+        );
         err!("A", String::from("'A' is never a valid character"));
         err!(">-", String::from("'>' must be preceded by '-'"));
         err!("-", String::from("'-' must be followed by '>'"));
         err!("-a", String::from("'-' must be followed by '>'"));
+        ok!(
+            "(assert x == y)",
+            vec![
+                Token::Open,
+                Token::Assert,
+                Token::Name('x'),
+                Token::Equals,
+                Token::Name('y'),
+                Token::Close
+            ]
+        );
+        ok!(
+            "asserts",
+            "asserts".chars().map(Token::Name).collect::<Vec<_>>()
+        );
+        ok!(
+            "xassert",
+            "xassert".chars().map(Token::Name).collect::<Vec<_>>()
+        );
+        ok!(
+            "(let x = y in x)",
+            vec![
+                Token::Open,
+                Token::Let,
+                Token::Name('x'),
+                Token::Bind,
+                Token::Name('y'),
+                Token::In,
+                Token::Name('x'),
+                Token::Close
+            ]
+        );
+        ok!(
+            "(x where rec x=y)",
+            vec![
+                Token::Open,
+                Token::Name('x'),
+                Token::Where,
+                Token::Rec,
+                Token::Name('x'),
+                Token::Bind,
+                Token::Name('y'),
+                Token::Close
+            ]
+        );
+        ok!("= ===", vec![Token::Bind, Token::Equals, Token::Bind]);
+        ok!("ni", vec![Token::Name('n'), Token::Name('i')]);
+        ok!(
+            "(x -> (in))",
+            vec![
+                Token::Open,
+                Token::Name('x'),
+                Token::Arrow,
+                Token::Open,
+                Token::Name('i'),
+                Token::Name('n'),
+                Token::Close,
+                Token::Close
+            ]
+        );
+        ok!(
+            "(re)",
+            vec![
+                Token::Open,
+                Token::Name('r'),
+                Token::Name('e'),
+                Token::Close
+            ]
+        );
+        ok!(
+            "(let)",
+            vec![
+                Token::Open,
+                Token::Name('l'),
+                Token::Name('e'),
+                Token::Name('t'),
+                Token::Close
+            ]
+        );
+        ok!(
+            "(f where)",
+            vec![
+                Token::Open,
+                Token::Name('f'),
+                Token::Name('w'),
+                Token::Name('h'),
+                Token::Name('e'),
+                Token::Name('r'),
+                Token::Name('e'),
+                Token::Close
+            ]
+        );
+        ok!(
+            "(let x = (in) in x)",
+            vec![
+                Token::Open,
+                Token::Let,
+                Token::Name('x'),
+                Token::Bind,
+                Token::Open,
+                Token::Name('i'),
+                Token::Name('n'),
+                Token::Close,
+                Token::In,
+                Token::Name('x'),
+                Token::Close
+            ]
+        );
+        ok!(
+            "(x rec y = z)",
+            vec![
+                Token::Open,
+                Token::Name('x'),
+                Token::Name('r'),
+                Token::Name('e'),
+                Token::Name('c'),
+                Token::Name('y'),
+                Token::Bind,
+                Token::Name('z'),
+                Token::Close
+            ]
+        );
+        err!("=>", String::from("'>' must be preceded by '-'"));
+    }
+
+    #[test]
+    fn spans() {
+        let spans: Vec<Span> = lex_spanned("(x -> y)")
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| span)
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span { start: 0, end: 1 },
+                Span { start: 1, end: 2 },
+                Span { start: 3, end: 5 },
+                Span { start: 6, end: 7 },
+                Span { start: 7, end: 8 },
+            ]
+        );
     }
 }
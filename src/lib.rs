@@ -0,0 +1,29 @@
+pub mod arena;
+pub mod binary;
+pub mod check;
+pub mod construct;
+pub mod debruijn;
+pub mod define;
+pub mod dot;
+pub mod eta;
+pub mod eval;
+pub mod free;
+pub mod fuzz;
+pub mod golden;
+pub mod infer;
+pub mod json;
+pub mod lex;
+pub mod lint;
+pub mod lsp;
+pub mod need;
+pub mod output;
+pub mod parse;
+pub mod pipeline;
+pub mod random;
+pub mod serialize;
+pub mod sexp;
+pub mod ski;
+pub mod source;
+pub mod systemf;
+pub mod typecheck;
+pub mod types;
@@ -0,0 +1,265 @@
+use crate::construct;
+use crate::construct::Term;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lint {
+    Shadowing,
+    UnusedParameter,
+    UnusedDefinition,
+    SelfApplication,
+}
+
+pub const LINTS: [Lint; 4] = [
+    Lint::Shadowing,
+    Lint::UnusedParameter,
+    Lint::UnusedDefinition,
+    Lint::SelfApplication,
+];
+
+impl Lint {
+    pub fn code(self) -> &'static str {
+        match self {
+            Lint::Shadowing => "L001",
+            Lint::UnusedParameter => "L002",
+            Lint::UnusedDefinition => "L003",
+            Lint::SelfApplication => "L004",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::Shadowing => "shadowing",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UnusedDefinition => "unused-definition",
+            Lint::SelfApplication => "self-application",
+        }
+    }
+
+    /// Looks a lint up by its code or its name.
+    pub fn find(key: &str) -> Option<Lint> {
+        LINTS
+            .iter()
+            .copied()
+            .find(|lint| lint.code() == key || lint.name() == key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    severities: [Severity; LINTS.len()],
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            severities: [Severity::Warn; LINTS.len()],
+        }
+    }
+}
+
+impl Options {
+    pub fn severity(&self, lint: Lint) -> Severity {
+        self.severities[lint as usize]
+    }
+
+    pub fn set(&mut self, lint: Lint, severity: Severity) {
+        self.severities[lint as usize] = severity;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub lint: Lint,
+    pub severity: Severity,
+    /// Preorder index of the offending term node.
+    pub index: usize,
+    pub message: String,
+}
+
+/// Runs every lint that is not allowed over a defined term, in preorder.
+pub fn lint(term: &Term, options: &Options) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        options,
+        scope: Vec::new(),
+        index: 0,
+        diagnostics: Vec::new(),
+    };
+    linter.visit(term);
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    options: &'a Options,
+    scope: Vec<char>,
+    index: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, lint: Lint, index: usize, message: String) {
+        let severity = self.options.severity(lint);
+        if severity != Severity::Allow {
+            self.diagnostics.push(Diagnostic {
+                lint,
+                severity,
+                index,
+                message,
+            });
+        }
+    }
+
+    fn bind(
+        &mut self,
+        name: char,
+        id: Option<usize>,
+        body: &Term,
+        index: usize,
+        unused: Lint,
+        kind: &str,
+    ) {
+        if self.scope.contains(&name) {
+            self.report(
+                Lint::Shadowing,
+                index,
+                format!("`{}` shadows an outer binder", name),
+            );
+        }
+        if !id.is_some_and(|id| uses(body, id)) {
+            self.report(unused, index, format!("unused {} `{}`", kind, name));
+        }
+        self.scope.push(name);
+        self.visit(body);
+        self.scope.pop();
+    }
+
+    fn visit(&mut self, term: &Term) {
+        let index = self.index;
+        self.index += 1;
+        match term {
+            Term::Var(..) => (),
+            Term::Lambda(name, id, _, body) => {
+                self.bind(*name, *id, body, index, Lint::UnusedParameter, "parameter")
+            }
+            Term::Def(name, id, body) => self.bind(
+                *name,
+                *id,
+                body,
+                index,
+                Lint::UnusedDefinition,
+                "definition",
+            ),
+            Term::Let(name, id, rec, value, body) => {
+                match &**value {
+                    // Skip the fixpoint a `let rec` wraps its value in.
+                    Term::App(fixpoint, func) if *rec => {
+                        self.index += 1 + construct::size(fixpoint);
+                        self.visit(func);
+                    }
+                    _ => self.visit(value),
+                }
+                self.bind(
+                    *name,
+                    *id,
+                    body,
+                    index,
+                    Lint::UnusedDefinition,
+                    "definition",
+                )
+            }
+            Term::App(func, arg) => {
+                if let (Term::Var(name, Some(id)), Term::Var(_, Some(arg_id))) = (&**func, &**arg) {
+                    if id == arg_id {
+                        self.report(
+                            Lint::SelfApplication,
+                            index,
+                            format!("self-application `({} {})`", name, name),
+                        );
+                    }
+                }
+                self.visit(func);
+                self.visit(arg);
+            }
+        }
+    }
+}
+
+fn uses(term: &Term, id: usize) -> bool {
+    match term {
+        Term::Var(_, var) => *var == Some(id),
+        Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => uses(body, id),
+        Term::App(func, arg) | Term::Let(_, _, _, func, arg) => uses(func, id) || uses(arg, id),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline;
+
+    macro_rules! lints {
+        ($text:literal, $expected:expr) => {
+            let (term, _) = pipeline::define_located($text).unwrap();
+            let lints: Vec<(Lint, usize)> = lint(&term, &Options::default())
+                .into_iter()
+                .map(|diagnostic| (diagnostic.lint, diagnostic.index))
+                .collect();
+            assert_eq!(lints, $expected);
+        };
+    }
+
+    #[test]
+    fn test() {
+        lints!("(x -> x)", vec![]);
+        lints!(
+            "(x -> (x -> x))",
+            vec![(Lint::UnusedParameter, 0), (Lint::Shadowing, 1)]
+        );
+        lints!("(x -> (y -> x))", vec![(Lint::UnusedParameter, 1)]);
+        lints!("(f : (x -> x))", vec![(Lint::UnusedDefinition, 0)]);
+        lints!("(f : (x -> (f x)))", vec![]);
+        lints!(
+            "((x -> (x x)) (x -> (x x)))",
+            vec![(Lint::SelfApplication, 2), (Lint::SelfApplication, 6)]
+        );
+        lints!("(x -> (y -> (x y)))", vec![]);
+        lints!("(y y)", vec![]);
+        lints!("(let rec f = (x -> (f x)) in f)", vec![]);
+        lints!("(x -> (let rec f = (y -> (f y)) in (f x)))", vec![]);
+    }
+
+    #[test]
+    fn severity() {
+        let mut options = Options::default();
+        options.set(Lint::find("L002").unwrap(), Severity::Allow);
+        options.set(Lint::find("shadowing").unwrap(), Severity::Deny);
+        let (term, _) = pipeline::define_located("(x -> (x -> x))").unwrap();
+        assert_eq!(
+            lint(&term, &options),
+            vec![Diagnostic {
+                lint: Lint::Shadowing,
+                severity: Severity::Deny,
+                index: 1,
+                message: String::from("`x` shadows an outer binder"),
+            }]
+        );
+        assert_eq!(Lint::find("L005"), None);
+        assert_eq!(
+            pipeline::lint("(x ->\n  (x -> (x x)))", &Options::default()),
+            Ok((
+                vec![
+                    String::from("1:1: warning[L002]: unused parameter `x`"),
+                    String::from("2:3: warning[L001]: `x` shadows an outer binder"),
+                    String::from("2:9: warning[L004]: self-application `(x x)`"),
+                ],
+                false
+            ))
+        );
+    }
+}
@@ -0,0 +1,865 @@
+//! A language server speaking the Language Server Protocol over stdio.
+
+use crate::construct::Term;
+use crate::debruijn;
+use crate::eval;
+use crate::infer;
+use crate::json;
+use crate::json::Json;
+use crate::lint;
+use crate::lint::Severity;
+use crate::pipeline;
+use crate::source;
+use crate::source::Span;
+use crate::typecheck;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Write;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Most beta steps taken when showing a normal form on hover.
+const HOVER_STEP_LIMIT: usize = 1000;
+
+type Error = (i64, String);
+
+/// Reads one `Content-Length` framed message, or `None` at the end.
+/// A malformed message is an inner error, so the server can answer it and read on.
+pub fn read_message(reader: &mut dyn BufRead) -> Result<Option<Result<Json, String>>, String> {
+    let mut length = Err(String::from("Missing Content-Length header"));
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).map_err(|err| err.to_string())? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid content length '{}'", value.trim()));
+        }
+    }
+    let length = match length {
+        Ok(length) => length,
+        Err(err) => return Ok(Some(Err(err))),
+    };
+    // Read rather than allocate up front, since the length is untrusted.
+    let mut body = Vec::new();
+    reader
+        .take(length as u64)
+        .read_to_end(&mut body)
+        .map_err(|err| err.to_string())?;
+    if body.len() < length {
+        return Err(String::from("Message ended before its Content-Length"));
+    }
+    let message = String::from_utf8(body)
+        .map_err(|err| err.to_string())
+        .and_then(|body| json::parse(&body));
+    Ok(Some(message))
+}
+
+pub fn write_message(writer: &mut dyn Write, message: &Json) -> Result<(), String> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| writer.flush())
+        .map_err(|err| err.to_string())
+}
+
+/// Handles messages from `reader` until `exit` or the end of the stream.
+pub fn serve(reader: &mut dyn BufRead, writer: &mut dyn Write) -> Result<(), String> {
+    let mut server = Server::default();
+    while let Some(message) = read_message(reader)? {
+        let replies = match message {
+            Ok(message) => server.handle(&message),
+            Err(err) => vec![response(&Json::Null, Err((PARSE_ERROR, err)))],
+        };
+        for reply in replies {
+            write_message(writer, &reply)?;
+        }
+        if server.exited {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+    exited: bool,
+}
+
+impl Server {
+    /// Returns the response to a request and any notifications to send.
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let result = match method {
+            "initialize" => Ok(capabilities()),
+            "initialized" => return Vec::new(),
+            "shutdown" => Ok(Json::Null),
+            "exit" => {
+                self.exited = true;
+                return Vec::new();
+            }
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                return self.synchronise(params).into_iter().collect();
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = uri(params) {
+                    self.documents.remove(uri);
+                }
+                return Vec::new();
+            }
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/rename" => self.rename(params),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+        };
+        match message.get("id") {
+            Some(id) => vec![response(id, result)],
+            None => Vec::new(),
+        }
+    }
+
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Stores an opened or changed document and returns its diagnostics.
+    fn synchronise(&mut self, params: &Json) -> Option<Json> {
+        let uri = uri(params)?;
+        let text = match params.get("contentChanges").and_then(Json::as_array) {
+            Some(changes) => changes.last()?.get("text")?.as_str()?,
+            None => params.get("textDocument")?.get("text")?.as_str()?,
+        };
+        self.documents.insert(String::from(uri), String::from(text));
+        Some(json::object(vec![
+            ("jsonrpc", json::string("2.0")),
+            ("method", json::string("textDocument/publishDiagnostics")),
+            (
+                "params",
+                json::object(vec![
+                    ("uri", json::string(uri)),
+                    ("diagnostics", Json::Array(diagnostics(text))),
+                ]),
+            ),
+        ]))
+    }
+
+    fn document<'a>(&'a self, params: &'a Json) -> Result<(&'a str, &'a str, usize), Error> {
+        let invalid = |message: &str| (INVALID_PARAMS, String::from(message));
+        let uri = uri(params).ok_or_else(|| invalid("Missing document"))?;
+        let text = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'", uri)))?;
+        let position = params
+            .get("position")
+            .ok_or_else(|| invalid("Missing position"))?;
+        let line = position.get("line").and_then(Json::as_usize);
+        let character = position.get("character").and_then(Json::as_usize);
+        match (line, character) {
+            (Some(line), Some(character)) => Ok((uri, text, offset(text, line, character))),
+            _ => Err(invalid("Invalid position")),
+        }
+    }
+
+    fn hover(&self, params: &Json) -> Result<Json, Error> {
+        let (_, text, offset) = self.document(params)?;
+        let analysis = match Analysis::new(text, offset) {
+            Some(analysis) => analysis,
+            None => return Ok(Json::Null),
+        };
+        let index = match analysis.node_at(offset) {
+            Some(index) => index,
+            None => return Ok(Json::Null),
+        };
+        let node = analysis.nodes()[index];
+        let mut term = node.clone();
+        let options = eval::Options {
+            limit: Some(HOVER_STEP_LIMIT),
+            ..eval::Options::default()
+        };
+        let mut lines = vec![match eval::eval_with(&mut term, &options) {
+            Ok(_) => format!("normal form: {}", term),
+            Err(err) => format!("normal form: {}", err),
+        }];
+        if let Ok(ty) = infer::infer(node) {
+            lines.push(format!("type: {}", ty));
+        }
+        Ok(json::object(vec![
+            (
+                "contents",
+                json::object(vec![
+                    ("kind", json::string("plaintext")),
+                    ("value", json::string(&lines.join("\n"))),
+                ]),
+            ),
+            ("range", range(text, analysis.spans[index])),
+        ]))
+    }
+
+    fn definition(&self, params: &Json) -> Result<Json, Error> {
+        let (uri, text, offset) = self.document(params)?;
+        let binder = Analysis::new(text, offset).and_then(|analysis| {
+            let binder = analysis.binder(analysis.bound_at(offset)?)?;
+            Some(analysis.name_span(binder))
+        });
+        Ok(match binder {
+            Some(span) => json::object(vec![
+                ("uri", json::string(uri)),
+                ("range", range(text, span)),
+            ]),
+            None => Json::Null,
+        })
+    }
+
+    fn rename(&self, params: &Json) -> Result<Json, Error> {
+        let (uri, text, offset) = self.document(params)?;
+        let invalid = |message: String| (INVALID_PARAMS, message);
+        let name = match params.get("newName").and_then(Json::as_str) {
+            Some(name) if name.len() == 1 && name.chars().all(|ch| ch.is_ascii_lowercase()) => name,
+            _ => {
+                return Err(invalid(String::from(
+                    "New name must be a letter from a to z",
+                )))
+            }
+        };
+        let bound = Analysis::new(text, offset).and_then(|analysis| {
+            let id = analysis.bound_at(offset)?;
+            let binder = analysis.binder(id)?;
+            Some((analysis, id, binder))
+        });
+        let (analysis, id, binder) =
+            bound.ok_or_else(|| invalid(String::from("Only bound variables can be renamed")))?;
+        let mut spans = vec![analysis.name_span(binder)];
+        for (node, span) in analysis.nodes().into_iter().zip(&analysis.spans) {
+            if let Term::Var(_, Some(var)) = node {
+                if *var == id {
+                    spans.push(*span);
+                }
+            }
+        }
+
+        let mut renamed: Vec<char> = text.chars().collect();
+        for span in &spans {
+            renamed.splice(span.start..span.end, name.chars());
+        }
+        let renamed: String = renamed.into_iter().collect();
+        let equivalent = Analysis::new(&renamed, offset)
+            .is_some_and(|renamed| debruijn::alpha_equivalent(&analysis.term, &renamed.term));
+        if !equivalent {
+            let old = text.chars().nth(spans[0].start).unwrap_or_default();
+            return Err(invalid(format!(
+                "Renaming `{}` to `{}` would capture a variable",
+                old, name
+            )));
+        }
+
+        let edits = spans
+            .into_iter()
+            .map(|span| {
+                json::object(vec![
+                    ("range", range(text, span)),
+                    ("newText", json::string(name)),
+                ])
+            })
+            .collect();
+        Ok(json::object(vec![(
+            "changes",
+            Json::Object(vec![(String::from(uri), Json::Array(edits))]),
+        )]))
+    }
+}
+
+fn capabilities() -> Json {
+    json::object(vec![(
+        "capabilities",
+        json::object(vec![
+            ("textDocumentSync", Json::Number(1.0)),
+            ("hoverProvider", Json::Bool(true)),
+            ("definitionProvider", Json::Bool(true)),
+            ("renameProvider", Json::Bool(true)),
+        ]),
+    )])
+}
+
+fn response(id: &Json, result: Result<Json, Error>) -> Json {
+    let payload = match result {
+        Ok(result) => ("result", result),
+        Err((code, message)) => (
+            "error",
+            json::object(vec![
+                ("code", Json::Number(code as f64)),
+                ("message", json::string(&message)),
+            ]),
+        ),
+    };
+    json::object(vec![
+        ("jsonrpc", json::string("2.0")),
+        ("id", id.clone()),
+        payload,
+    ])
+}
+
+fn uri(params: &Json) -> Option<&str> {
+    params.get("textDocument")?.get("uri")?.as_str()
+}
+
+/// Every error and lint in a document. Lex errors cover all of it.
+fn diagnostics(text: &str) -> Vec<Json> {
+    let (fragments, errors) = match pipeline::recover(text) {
+        Ok(recovered) => recovered,
+        Err(err) => {
+            let whole = Span {
+                start: 0,
+                end: text.chars().count(),
+            };
+            return vec![diagnostic(text, whole, Severity::Deny, None, &err)];
+        }
+    };
+    let mut diagnostics: Vec<Json> = errors
+        .iter()
+        .map(|(span, err)| diagnostic(text, *span, Severity::Deny, None, err))
+        .collect();
+    for (term, spans) in fragments {
+        if let Err((index, err)) = typecheck::typecheck(&term) {
+            diagnostics.push(diagnostic(text, spans[index], Severity::Deny, None, &err));
+        }
+        for lint in lint::lint(&term, &lint::Options::default()) {
+            diagnostics.push(diagnostic(
+                text,
+                spans[lint.index],
+                lint.severity,
+                Some(lint.lint.code()),
+                &lint.message,
+            ));
+        }
+    }
+    diagnostics
+}
+
+fn diagnostic(
+    text: &str,
+    span: Span,
+    severity: Severity,
+    code: Option<&str>,
+    message: &str,
+) -> Json {
+    let severity = match severity {
+        Severity::Deny => 1.0,
+        _ => 2.0,
+    };
+    let mut fields = vec![
+        ("range", range(text, span)),
+        ("severity", Json::Number(severity)),
+        ("source", json::string("lambda")),
+        ("message", json::string(message)),
+    ];
+    if let Some(code) = code {
+        fields.push(("code", json::string(code)));
+    }
+    json::object(fields)
+}
+
+fn range(text: &str, span: Span) -> Json {
+    json::object(vec![
+        ("start", position(text, span.start)),
+        ("end", position(text, span.end)),
+    ])
+}
+
+fn position(text: &str, offset: usize) -> Json {
+    let (line, column) = source::position(text, offset);
+    json::object(vec![
+        ("line", Json::Number((line - 1) as f64)),
+        ("character", Json::Number((column - 1) as f64)),
+    ])
+}
+
+/// Character offset of a line and character, clamped to the line.
+fn offset(text: &str, line: usize, character: usize) -> usize {
+    let mut offset = 0;
+    for (index, text) in text.split('\n').enumerate() {
+        let length = text.chars().count();
+        if index == line {
+            return offset + character.min(length);
+        }
+        offset += length + 1;
+    }
+    offset.saturating_sub(1)
+}
+
+/// The defined term of a document around some offset, with its spans.
+struct Analysis<'a> {
+    text: &'a str,
+    term: Term,
+    spans: Vec<Span>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(text: &'a str, offset: usize) -> Option<Analysis<'a>> {
+        let (fragments, _) = pipeline::recover(text).ok()?;
+        let (term, spans) = fragments
+            .into_iter()
+            .find(|(_, spans)| spans[0].start <= offset && offset < spans[0].end)?;
+        Some(Analysis { text, term, spans })
+    }
+
+    fn nodes(&self) -> Vec<&Term> {
+        let mut nodes = Vec::new();
+        preorder(&self.term, &mut nodes);
+        nodes
+    }
+
+    /// Index of the innermost node whose span contains `offset`.
+    fn node_at(&self, offset: usize) -> Option<usize> {
+        self.spans
+            .iter()
+            .rposition(|span| span.start <= offset && offset < span.end)
+    }
+
+    /// Id of the binder named at `offset` by a variable or the binder.
+    fn bound_at(&self, offset: usize) -> Option<usize> {
+        let index = self.node_at(offset)?;
+        let span = self.spans[index];
+        match self.nodes()[index] {
+            // The fixpoint of a `let rec` spans the whole binding.
+            Term::Var(_, id) if span.end - span.start == 1 => *id,
+            Term::Var(..) => None,
+            Term::Lambda(_, id, _, _) | Term::Def(_, id, _) | Term::Let(_, id, _, _, _) => {
+                let span = self.name_span(index);
+                if span.start <= offset && offset < span.end {
+                    *id
+                } else {
+                    None
+                }
+            }
+            Term::App(..) => None,
+        }
+    }
+
+    /// Index of the lambda or definition with `id`.
+    fn binder(&self, id: usize) -> Option<usize> {
+        self.nodes().iter().position(|node| match node {
+            Term::Lambda(_, binder, _, _)
+            | Term::Def(_, binder, _)
+            | Term::Let(_, binder, _, _, _) => *binder == Some(id),
+            _ => false,
+        })
+    }
+
+    /// Span of the name of the binder at `index`.
+    fn name_span(&self, index: usize) -> Span {
+        let span = self.spans[index];
+        if let Term::Let(..) = self.nodes()[index] {
+            return self.bound_name_span(span);
+        }
+        if self.text.chars().nth(span.start) != Some('(') {
+            return Span {
+                start: span.start,
+                end: span.start + 1,
+            };
+        }
+        let start = self
+            .text
+            .chars()
+            .enumerate()
+            .skip(span.start + 1)
+            .find(|(_, ch)| ch.is_ascii_lowercase())
+            .map_or(span.start, |(start, _)| start);
+        Span {
+            start,
+            end: start + 1,
+        }
+    }
+
+    fn bound_name_span(&self, span: Span) -> Span {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut depth = 0;
+        let mut name = span.start;
+        for (index, ch) in chars.iter().enumerate().take(span.end).skip(span.start + 1) {
+            match ch {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                '=' if depth == 0 && chars.get(index + 1) != Some(&'=') => break,
+                'a'..='z' if depth == 0 => name = index,
+                _ => (),
+            }
+        }
+        Span {
+            start: name,
+            end: name + 1,
+        }
+    }
+}
+
+fn preorder<'a>(term: &'a Term, nodes: &mut Vec<&'a Term>) {
+    nodes.push(term);
+    match term {
+        Term::Var(..) => (),
+        Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => preorder(body, nodes),
+        Term::App(func, arg) | Term::Let(_, _, _, func, arg) => {
+            preorder(func, nodes);
+            preorder(arg, nodes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const URI: &str = "file:///test.lam";
+
+    /// Drives a server in process, numbering requests from one.
+    struct Client {
+        server: Server,
+        next: usize,
+    }
+
+    impl Client {
+        fn new() -> Client {
+            Client {
+                server: Server::default(),
+                next: 1,
+            }
+        }
+
+        fn notify(&mut self, method: &str, params: Json) -> Vec<Json> {
+            self.server.handle(&json::object(vec![
+                ("jsonrpc", json::string("2.0")),
+                ("method", json::string(method)),
+                ("params", params),
+            ]))
+        }
+
+        fn request(&mut self, method: &str, params: Json) -> Json {
+            let id = self.next;
+            self.next += 1;
+            let mut replies = self.server.handle(&json::object(vec![
+                ("jsonrpc", json::string("2.0")),
+                ("id", Json::Number(id as f64)),
+                ("method", json::string(method)),
+                ("params", params),
+            ]));
+            assert_eq!(replies.len(), 1);
+            let reply = replies.remove(0);
+            assert_eq!(reply.get("id"), Some(&Json::Number(id as f64)));
+            reply
+        }
+
+        fn open(&mut self, text: &str) -> Json {
+            let document = json::object(vec![
+                ("uri", json::string(URI)),
+                ("text", json::string(text)),
+            ]);
+            let mut replies = self.notify(
+                "textDocument/didOpen",
+                json::object(vec![("textDocument", document)]),
+            );
+            assert_eq!(replies.len(), 1);
+            replies.remove(0)
+        }
+
+        fn at(
+            &mut self,
+            method: &str,
+            line: usize,
+            character: usize,
+            extra: Vec<(&str, Json)>,
+        ) -> Json {
+            let mut params = vec![
+                (
+                    "textDocument",
+                    json::object(vec![("uri", json::string(URI))]),
+                ),
+                (
+                    "position",
+                    json::object(vec![
+                        ("line", Json::Number(line as f64)),
+                        ("character", Json::Number(character as f64)),
+                    ]),
+                ),
+            ];
+            params.extend(extra);
+            self.request(method, json::object(params))
+        }
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut client = Client::new();
+        let reply = client.request("initialize", json::object(vec![]));
+        assert_eq!(
+            reply.to_string(),
+            r#"{"jsonrpc":"2.0","id":1,"result":{"capabilities":{"textDocumentSync":1,"hoverProvider":true,"definitionProvider":true,"renameProvider":true}}}"#
+        );
+        assert_eq!(client.notify("initialized", json::object(vec![])), vec![]);
+        let reply = client.request("textDocument/formatting", json::object(vec![]));
+        assert_eq!(
+            reply.get("error").and_then(|error| error.get("code")),
+            Some(&Json::Number(-32601.0))
+        );
+        assert_eq!(
+            client.request("shutdown", Json::Null).get("result"),
+            Some(&Json::Null)
+        );
+        assert_eq!(client.notify("exit", Json::Null), vec![]);
+        assert!(client.server.exited());
+    }
+
+    #[test]
+    fn framing() {
+        let mut input = Vec::new();
+        write_message(
+            &mut input,
+            &parse_json(r#"{"jsonrpc":"2.0","id":7,"method":"shutdown"}"#),
+        )
+        .unwrap();
+        write_message(
+            &mut input,
+            &parse_json(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        serve(&mut input.as_slice(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Content-Length: 38\r\n\r\n{\"jsonrpc\":\"2.0\",\"id\":7,\"result\":null}"
+        );
+        assert_eq!(
+            read_message(&mut "Content-Length: x\r\n\r\n".as_bytes()),
+            Ok(Some(Err(String::from("Invalid content length 'x'"))))
+        );
+        assert_eq!(
+            read_message(&mut "Content-Length: 18446744073709551615\r\n\r\n{}".as_bytes()),
+            Err(String::from("Message ended before its Content-Length"))
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let mut input = Vec::from(&b"Content-Length: 3\r\n\r\n{x}"[..]);
+        write_message(
+            &mut input,
+            &parse_json(r#"{"jsonrpc":"2.0","id":1,"method":"shutdown"}"#),
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        serve(&mut input.as_slice(), &mut output).unwrap();
+        let mut output = output.as_slice();
+        let error = read_message(&mut output).unwrap().unwrap().unwrap();
+        assert_eq!(error.get("id"), Some(&Json::Null));
+        assert_eq!(
+            error.get("error").and_then(|error| error.get("code")),
+            Some(&Json::Number(-32700.0))
+        );
+        let reply = read_message(&mut output).unwrap().unwrap().unwrap();
+        assert_eq!(
+            reply.to_string(),
+            r#"{"jsonrpc":"2.0","id":1,"result":null}"#
+        );
+    }
+
+    fn parse_json(text: &str) -> Json {
+        json::parse(text).unwrap()
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut client = Client::new();
+        assert_eq!(
+            client.open("(x -> y z)").to_string(),
+            r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///test.lam","diagnostics":[{"range":{"start":{"line":0,"character":0},"end":{"line":0,"character":10}},"severity":1,"source":"lambda","message":"Lambda body has too many terms"}]}}"#
+        );
+        let published = client.open("(x -> (x -> x))");
+        let diagnostics = published
+            .get("params")
+            .and_then(|params| params.get("diagnostics"))
+            .and_then(Json::as_array)
+            .unwrap();
+        let codes: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.get("code").and_then(Json::as_str).unwrap())
+            .collect();
+        assert_eq!(codes, vec!["L002", "L001"]);
+        assert_eq!(
+            diagnostics[1].get("range").unwrap().to_string(),
+            r#"{"start":{"line":0,"character":6},"end":{"line":0,"character":14}}"#
+        );
+        let published = client.open("((x [a] -> x) (y [a -> a] -> y))");
+        let diagnostics = published
+            .get("params")
+            .and_then(|params| params.get("diagnostics"))
+            .and_then(Json::as_array)
+            .unwrap();
+        assert_eq!(diagnostics[0].get("severity"), Some(&Json::Number(1.0)));
+    }
+
+    #[test]
+    fn recovery() {
+        let mut client = Client::new();
+        let published = client.open("(x -> y z)\n(f (g))\n(h -> (k -> h))");
+        let messages: Vec<_> = published
+            .get("params")
+            .and_then(|params| params.get("diagnostics"))
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .map(|diagnostic| diagnostic.get("message").and_then(Json::as_str).unwrap())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Lambda body has too many terms",
+                "Expected more symbols after name",
+                "unused parameter `k`"
+            ]
+        );
+        let hover = client.at("textDocument/hover", 2, 0, vec![]);
+        let contents = hover
+            .get("result")
+            .and_then(|result| result.get("contents"))
+            .unwrap();
+        assert_eq!(
+            contents.get("value").and_then(Json::as_str),
+            Some("normal form: (h -> (k -> h))\ntype: a -> b -> a")
+        );
+        let hover = client.at("textDocument/hover", 0, 0, vec![]);
+        assert_eq!(hover.get("result"), Some(&Json::Null));
+    }
+
+    #[test]
+    fn navigation() {
+        let mut client = Client::new();
+        client.open("(f ->\n  (x -> (f (f x))))");
+
+        let hover = client.at("textDocument/hover", 1, 2, vec![]);
+        let contents = hover
+            .get("result")
+            .and_then(|result| result.get("contents"))
+            .unwrap();
+        assert_eq!(
+            contents.get("value").and_then(Json::as_str),
+            Some("normal form: (x -> (f (f x)))")
+        );
+        let hover = client.at("textDocument/hover", 0, 0, vec![]);
+        let contents = hover
+            .get("result")
+            .and_then(|result| result.get("contents"))
+            .unwrap();
+        assert_eq!(
+            contents.get("value").and_then(Json::as_str),
+            Some("normal form: (f -> (x -> (f (f x))))\ntype: (a -> a) -> a -> a")
+        );
+
+        let definition = client.at("textDocument/definition", 1, 14, vec![]);
+        assert_eq!(
+            definition.get("result").unwrap().to_string(),
+            r#"{"uri":"file:///test.lam","range":{"start":{"line":1,"character":3},"end":{"line":1,"character":4}}}"#
+        );
+        let definition = client.at("textDocument/definition", 1, 9, vec![]);
+        assert_eq!(
+            definition
+                .get("result")
+                .and_then(|result| result.get("range"))
+                .unwrap()
+                .to_string(),
+            r#"{"start":{"line":0,"character":1},"end":{"line":0,"character":2}}"#
+        );
+        let definition = client.at("textDocument/definition", 1, 8, vec![]);
+        assert_eq!(definition.get("result"), Some(&Json::Null));
+
+        client.open("((f f) where f = (y -> y))");
+        let definition = client.at("textDocument/definition", 0, 2, vec![]);
+        assert_eq!(
+            definition
+                .get("result")
+                .and_then(|result| result.get("range"))
+                .unwrap()
+                .to_string(),
+            r#"{"start":{"line":0,"character":13},"end":{"line":0,"character":14}}"#
+        );
+    }
+
+    #[test]
+    fn rename() {
+        let mut client = Client::new();
+        client.open("(f -> (x -> (f (f x))))");
+        let rename = client.at(
+            "textDocument/rename",
+            0,
+            1,
+            vec![("newName", json::string("g"))],
+        );
+        let edits = rename
+            .get("result")
+            .and_then(|result| result.get("changes"))
+            .and_then(|changes| changes.get(URI))
+            .and_then(Json::as_array)
+            .unwrap();
+        let starts: Vec<_> = edits
+            .iter()
+            .map(|edit| {
+                assert_eq!(edit.get("newText").and_then(Json::as_str), Some("g"));
+                let start = edit
+                    .get("range")
+                    .and_then(|range| range.get("start"))
+                    .unwrap();
+                start.get("character").and_then(Json::as_usize).unwrap()
+            })
+            .collect();
+        assert_eq!(starts, vec![1, 13, 16]);
+
+        let rename = client.at(
+            "textDocument/rename",
+            0,
+            18,
+            vec![("newName", json::string("f"))],
+        );
+        assert_eq!(
+            rename
+                .get("error")
+                .and_then(|error| error.get("message"))
+                .and_then(Json::as_str),
+            Some("Renaming `x` to `f` would capture a variable")
+        );
+        let rename = client.at(
+            "textDocument/rename",
+            0,
+            1,
+            vec![("newName", json::string("fx"))],
+        );
+        assert_eq!(
+            rename
+                .get("error")
+                .and_then(|error| error.get("message"))
+                .and_then(Json::as_str),
+            Some("New name must be a letter from a to z")
+        );
+
+        client.open("(let rec f = (x -> f) in f)");
+        let rename = client.at(
+            "textDocument/rename",
+            0,
+            0,
+            vec![("newName", json::string("g"))],
+        );
+        assert_eq!(
+            rename
+                .get("error")
+                .and_then(|error| error.get("message"))
+                .and_then(Json::as_str),
+            Some("Only bound variables can be renamed")
+        );
+    }
+}
@@ -1,32 +1,628 @@
-mod construct;
-mod define;
-mod eval;
-mod lex;
-mod output;
-mod parse;
-mod pipeline;
-
-use pipeline::pipeline;
-use pipeline::Pipeline;
-use pipeline::PipelineResult;
+use lambda::arena;
+use lambda::binary;
+use lambda::construct::Term;
+use lambda::dot;
+use lambda::eval;
+use lambda::free;
+use lambda::golden;
+use lambda::lint;
+use lambda::lint::Lint;
+use lambda::lint::Severity;
+use lambda::lsp;
+use lambda::need;
+use lambda::output;
+use lambda::output::Notation;
+use lambda::pipeline;
+use lambda::pipeline::pipeline;
+use lambda::pipeline::Pipeline;
+use lambda::pipeline::PipelineResult;
+use lambda::serialize;
+use lambda::ski;
+use lambda::systemf;
 use std::io;
 use std::io::BufRead;
+use std::io::Read;
+use std::path::Path;
+
+enum Command {
+    Encode,
+    Decode,
+    Fmt,
+    Lsp,
+    Test,
+}
+
+#[derive(Default, PartialEq)]
+enum Format {
+    #[default]
+    Text,
+    Json,
+    Sexp,
+}
+
+#[derive(Default)]
+struct Options {
+    stage: Option<Pipeline>,
+    format: Format,
+    command: Option<Command>,
+    arena: bool,
+    bless: bool,
+    check: bool,
+    dot: bool,
+    dot_reductions: Option<usize>,
+    ski: bool,
+    infer: bool,
+    lint: Option<lint::Options>,
+    need: bool,
+    strict: bool,
+    warn_free: bool,
+    width: Option<usize>,
+    notation: Option<Box<dyn Notation>>,
+    stats: bool,
+    system_f: bool,
+    trace: bool,
+    eval: eval::Options,
+    ski_options: ski::Options,
+}
 
 fn main() {
-    if let Some(input) = std::env::args().nth(1) {
-        run(&input);
+    let mut options = Options::default();
+    let mut input = None;
+    let mut flags = Vec::new();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            flags.push(arg.clone());
+        }
+        match arg.as_str() {
+            "--allow" => options.set_lint(args.next(), Severity::Allow),
+            "--arena" => options.arena = true,
+            "--bless" => options.bless = true,
+            "--bc" => options.ski_options.bc = true,
+            "--check" => options.check = true,
+            "--deny" => options.set_lint(args.next(), Severity::Deny),
+            "--dot" => options.dot = true,
+            "--dot-reductions" => {
+                options.dot_reductions = match args.next().map(|bound| bound.parse()) {
+                    Some(Ok(bound)) => Some(bound),
+                    _ => usage("'--dot-reductions' must be followed by a number of terms"),
+                }
+            }
+            "--eta" => options.eval.eta = true,
+            "--format" => {
+                options.format = match args.next().as_deref() {
+                    Some("json") => Format::Json,
+                    Some("sexp") => Format::Sexp,
+                    Some("text") => Format::Text,
+                    _ => usage("'--format' must be followed by 'json', 'sexp' or 'text'"),
+                }
+            }
+            "--infer" => options.infer = true,
+            "--lint" => {
+                options.lint.get_or_insert_with(lint::Options::default);
+            }
+            "--mode" => {
+                options.eval.mode = match args.next().as_deref() {
+                    Some("whnf") => eval::Mode::WeakHead,
+                    Some("hnf") => eval::Mode::Head,
+                    Some("nf") => eval::Mode::Normal,
+                    _ => usage("'--mode' must be followed by 'whnf', 'hnf' or 'nf'"),
+                }
+            }
+            "--need" => options.need = true,
+            "--notation" => {
+                options.notation = match args.next().as_deref() {
+                    Some("ascii") => Some(Box::new(output::Ascii)),
+                    Some("unicode") => Some(Box::new(output::Unicode)),
+                    Some("latex") => Some(Box::new(output::Latex)),
+                    _ => usage("'--notation' must be followed by 'ascii', 'unicode' or 'latex'"),
+                }
+            }
+            "--ski" => options.ski = true,
+            "--stage" => {
+                options.stage = Some(match args.next().as_deref() {
+                    Some("lex") => Pipeline::Lex,
+                    Some("parse") => Pipeline::Parse,
+                    Some("construct") => Pipeline::Construct,
+                    Some("define") => Pipeline::Define,
+                    Some("typecheck") => Pipeline::Typecheck,
+                    Some("eval") => Pipeline::Eval,
+                    _ => usage(
+                        "'--stage' must be followed by 'lex', 'parse', 'construct', 'define', 'typecheck' or 'eval'",
+                    ),
+                })
+            }
+            "--stats" => options.stats = true,
+            "--strict" => options.strict = true,
+            "--system-f" => options.system_f = true,
+            "--trace" => options.trace = true,
+            "--width" => {
+                options.width = match args.next().map(|width| width.parse()) {
+                    Some(Ok(width)) => Some(width),
+                    _ => usage("'--width' must be followed by a number of columns"),
+                }
+            }
+            "--warn" => options.set_lint(args.next(), Severity::Warn),
+            "--warn-free" => options.warn_free = true,
+            _ if arg.starts_with("--") => usage(&format!("Unknown flag '{}'", arg)),
+            "encode" if options.command.is_none() && input.is_none() => {
+                options.command = Some(Command::Encode)
+            }
+            "decode" if options.command.is_none() && input.is_none() => {
+                options.command = Some(Command::Decode)
+            }
+            "fmt" if options.command.is_none() && input.is_none() => {
+                options.command = Some(Command::Fmt)
+            }
+            "lsp" if options.command.is_none() && input.is_none() => {
+                options.command = Some(Command::Lsp)
+            }
+            "test" if options.command.is_none() && input.is_none() => {
+                options.command = Some(Command::Test)
+            }
+            _ => input = Some(arg),
+        }
+    }
+
+    check_flags(&flags, &options);
+
+    match options.command {
+        Some(Command::Fmt) => return run_fmt(input.as_deref(), &options),
+        Some(Command::Lsp) => return run_lsp(),
+        Some(Command::Test) => return run_test(input.as_deref(), &options),
+        _ => (),
+    }
+
+    let mut denied = false;
+    if let Some(input) = input {
+        run(&input, &options, &mut denied);
     } else {
         let stdin = io::stdin();
         for input in stdin.lock().lines() {
-            run(&input.unwrap());
+            match input {
+                Ok(input) => run(&input, &options, &mut denied),
+                Err(err) => return eprintln!("{}", err),
+            }
+        }
+    }
+    if denied {
+        std::process::exit(1);
+    }
+}
+
+impl Options {
+    fn notation(&self) -> &dyn Notation {
+        self.notation.as_deref().unwrap_or(&output::Ascii)
+    }
+
+    fn set_lint(&mut self, key: Option<String>, severity: Severity) {
+        match key.as_deref().and_then(Lint::find) {
+            Some(lint) => self
+                .lint
+                .get_or_insert_with(lint::Options::default)
+                .set(lint, severity),
+            None => {
+                usage("'--allow', '--warn' and '--deny' must be followed by a lint code or name")
+            }
+        }
+    }
+}
+
+// Flags choosing what is done with the input. Flags in the same group combine.
+const MODES: [&[&str]; 7] = [
+    &["--stage", "--format"],
+    &["--dot", "--dot-reductions"],
+    &["--infer"],
+    &["--system-f"],
+    &["--need"],
+    &["--arena"],
+    &["--ski"],
+];
+
+// The modes a flag applies to, "" being evaluation, or every mode when empty.
+fn applies(flag: &str) -> &'static [&'static str] {
+    match flag {
+        "--stats" => &["", "--arena"],
+        "--trace" | "--eta" | "--mode" | "--notation" => &[""],
+        "--bc" => &["--ski"],
+        "--width" | "--check" => &["fmt"],
+        "--bless" => &["test"],
+        _ => &[],
+    }
+}
+
+fn check_flags(flags: &[String], options: &Options) {
+    let mut mode = match options.command {
+        Some(Command::Encode) => "encode",
+        Some(Command::Decode) => "decode",
+        Some(Command::Fmt) => "fmt",
+        Some(Command::Lsp) => "lsp",
+        Some(Command::Test) => "test",
+        None => "",
+    };
+    for flag in flags {
+        if let Some(group) = MODES.iter().find(|group| group.contains(&flag.as_str())) {
+            if mode.is_empty() {
+                mode = flag;
+            } else if !group.contains(&mode) {
+                usage(&format!("'{}' cannot be combined with '{}'", flag, mode));
+            }
+        }
+    }
+    // Free variable checks and lints run before every mode but System F.
+    let checks = [
+        "--strict",
+        "--warn-free",
+        "--lint",
+        "--allow",
+        "--warn",
+        "--deny",
+    ];
+    for flag in flags {
+        if checks.contains(&flag.as_str()) && (mode == "--system-f" || options.command.is_some()) {
+            usage(&format!("'{}' cannot be combined with '{}'", flag, mode));
+        }
+        let modes = applies(flag);
+        if !modes.is_empty() && !modes.contains(&mode) {
+            let modes: Vec<String> = modes
+                .iter()
+                .map(|mode| match *mode {
+                    "" => String::from("evaluation"),
+                    mode => format!("'{}'", mode),
+                })
+                .collect();
+            usage(&format!(
+                "'{}' only applies to {}",
+                flag,
+                modes.join(" and ")
+            ));
         }
     }
 }
 
-fn run(input: &str) {
-    match pipeline(Pipeline::Eval, &input) {
-        Ok(PipelineResult::Term(result)) => println!("{}", result),
+fn usage(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+fn run(input: &str, options: &Options, denied: &mut bool) {
+    match options.command {
+        Some(Command::Encode) => return run_encode(input),
+        Some(Command::Decode) => return run_decode(input),
+        Some(Command::Fmt) | Some(Command::Lsp) | Some(Command::Test) | None => (),
+    }
+    if input.starts_with(':') {
+        return run_command(input);
+    }
+    if !options.system_f {
+        if options.strict {
+            if let Err(err) = pipeline::check_closed(input) {
+                return println!("{}", err);
+            }
+        } else if options.warn_free {
+            if let Ok(term) = pipeline::term(Pipeline::Define, input) {
+                let names = free::free_names(&term);
+                if !names.is_empty() {
+                    println!("warning: free variables: {}", list(&names));
+                }
+            }
+        }
+        if let Some(lint) = &options.lint {
+            match pipeline::lint(input, lint) {
+                Ok((messages, deny)) => {
+                    for message in messages {
+                        println!("{}", message);
+                    }
+                    if deny {
+                        *denied = true;
+                        return;
+                    }
+                }
+                Err(err) => return println!("{}", err),
+            }
+        }
+    }
+    if options.stage.is_some() || options.format != Format::Text {
+        return run_stage(input, options);
+    }
+    if options.dot || options.dot_reductions.is_some() {
+        return run_dot(input, options);
+    }
+    if options.infer {
+        return run_infer(input);
+    }
+    if options.system_f {
+        return run_system_f(input);
+    }
+    if options.need {
+        return run_need(input);
+    }
+    if options.arena {
+        return run_arena(input, options.stats);
+    }
+    if options.ski {
+        return run_ski(input, &options.ski_options);
+    }
+
+    let mut term = match pipeline::term(Pipeline::Typecheck, input) {
+        Ok(term) => term,
+        Err(err) => return println!("{}", err),
+    };
+    let notation = options.notation();
+    let result = if options.trace {
+        eval::trace(&mut term, &options.eval, &mut |step| {
+            print_term(step, notation)
+        })
+    } else {
+        eval::eval_with(&mut term, &options.eval)
+    };
+    match result {
+        Ok(stats) => {
+            if !options.trace {
+                print_term(&term, notation);
+            }
+            if options.stats {
+                println!("{}", stats);
+            }
+        }
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn print_term(term: &Term, notation: &dyn Notation) {
+    match output::render(term, notation) {
+        Ok(out) => println!("{}", out),
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn run_command(input: &str) {
+    let (command, input) = input.split_at(input.find(' ').unwrap_or(input.len()));
+    match command {
+        ":free" => run_free(input.trim_start()),
+        ":type" => run_infer(input.trim_start()),
+        ":prelude" => run_prelude(input.trim_start()),
+        _ => println!("Unknown command '{}'", command),
+    }
+}
+
+fn run_prelude(name: &str) {
+    if name.is_empty() {
+        for (name, _, ty) in systemf::PRELUDE {
+            println!("{} : {}", name, ty);
+        }
+    } else {
+        match systemf::prelude(name) {
+            Some(source) => println!("{}", source),
+            None => println!("Unknown prelude definition '{}'", name),
+        }
+    }
+}
+
+fn run_stage(input: &str, options: &Options) {
+    let result = match pipeline(options.stage.unwrap_or(Pipeline::Eval), input) {
+        Ok(result) => result,
+        Err(err) => return println!("{}", err),
+    };
+    match (&options.format, &result) {
+        (Format::Json, _) => println!("{}", serialize::to_json(&result)),
+        (Format::Sexp, _) => println!("{}", serialize::to_sexp(&result)),
+        (Format::Text, PipelineResult::Tokens(tokens)) => println!("{:?}", tokens),
+        (Format::Text, PipelineResult::Tree(tree)) => println!("{:?}", tree),
+        (Format::Text, PipelineResult::Term(term)) => println!("{}", term),
+    }
+}
+
+fn run_dot(input: &str, options: &Options) {
+    let term = match pipeline::term(Pipeline::Typecheck, input) {
+        Ok(term) => term,
+        Err(err) => return println!("{}", err),
+    };
+    match options.dot_reductions {
+        Some(bound) => match dot::reductions(&term, bound) {
+            Ok(graph) => print!("{}", graph),
+            Err(err) => println!("{}", err),
+        },
+        None => print!("{}", dot::ast(&term)),
+    }
+}
+
+fn run_free(input: &str) {
+    match pipeline::term(Pipeline::Define, input) {
+        Ok(term) => match free::free_names(&term).as_slice() {
+            [] => println!("no free variables"),
+            names => println!("{}", list(names)),
+        },
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn list(names: &[char]) -> String {
+    names
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn run_infer(input: &str) {
+    match pipeline::infer(input) {
+        Ok(ty) => println!("{}", ty),
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn run_system_f(input: &str) {
+    match pipeline::system_f(input) {
+        Ok((term, ty)) => {
+            println!("{}", term);
+            println!("{}", ty);
+        }
+        Err(err) => println!("{}", err),
+    }
+}
+
+/// Formats `path` or standard input. With `--check` nothing is written.
+fn run_fmt(path: Option<&str>, options: &Options) {
+    let input = match path {
+        Some(path) => std::fs::read_to_string(path),
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input).map(|_| input)
+        }
+    };
+    let input = match input {
+        Ok(input) => input,
+        Err(err) => usage(&err.to_string()),
+    };
+    let formatted = match pipeline::format(&input, options.width.unwrap_or(80)) {
+        Ok(formatted) => formatted,
+        Err(err) => usage(&err),
+    };
+    if options.check {
+        if formatted != input {
+            usage(&format!("{} is not formatted", path.unwrap_or("<stdin>")));
+        }
+    } else if let Some(path) = path {
+        if let Err(err) = std::fs::write(path, formatted) {
+            usage(&err.to_string());
+        }
+    } else {
+        print!("{}", formatted);
+    }
+}
+
+fn run_lsp() {
+    if let Err(err) = lsp::serve(&mut io::stdin().lock(), &mut io::stdout()) {
+        usage(&err);
+    }
+}
+
+fn run_test(dir: Option<&str>, options: &Options) {
+    let dir = dir.unwrap_or_else(|| usage("'test' must be followed by a directory or file"));
+    let results = match golden::run(Path::new(dir), options.bless) {
+        Ok(results) => results,
+        Err(err) => usage(&err),
+    };
+    let mut assertions = (0, 0);
+    for (program, status) in &results {
+        match status {
+            golden::Status::Passed => println!("ok {}", program.display()),
+            golden::Status::Blessed => println!("blessed {}", program.display()),
+            golden::Status::Failed { expected, actual } => {
+                println!("FAILED {}", program.display());
+                print!("expected:\n{}actual:\n{}", expected, actual);
+            }
+            golden::Status::Missing { actual } => {
+                println!("MISSING {}", program.display());
+                print!("actual:\n{}", actual);
+            }
+            golden::Status::Checked(report) => {
+                assertions.0 += report.passed;
+                assertions.1 += report.failures.len();
+                if report.failures.is_empty() {
+                    println!("ok {} ({} assertions)", program.display(), report.passed);
+                } else {
+                    println!("FAILED {}", program.display());
+                    for failure in &report.failures {
+                        println!("{}", failure);
+                    }
+                }
+            }
+        }
+    }
+    let failed = results
+        .iter()
+        .filter(|(_, status)| !status.passed())
+        .count();
+    println!("{} passed; {} failed", results.len() - failed, failed);
+    if assertions != (0, 0) {
+        println!(
+            "assertions: {} passed; {} failed",
+            assertions.0, assertions.1
+        );
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn run_encode(input: &str) {
+    let term = match pipeline::term(Pipeline::Define, input) {
+        Ok(term) => term,
+        Err(err) => return println!("{}", err),
+    };
+    match binary::encode(&term) {
+        Ok(bits) => {
+            println!("{}", bits);
+            println!("size: {} bits", bits.len());
+        }
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn run_decode(input: &str) {
+    match binary::decode(input.trim()) {
+        Ok(term) => println!("{}", term),
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn run_need(input: &str) {
+    let mut term = match pipeline::term(Pipeline::Typecheck, input) {
+        Ok(term) => term,
+        Err(err) => return println!("{}", err),
+    };
+    match need::compare(&mut term) {
+        Ok(sharing) => {
+            println!("{}", term);
+            println!(
+                "beta steps: {} (tree evaluator: {}, saved: {})",
+                sharing.need_steps,
+                sharing.tree_steps,
+                sharing.saved()
+            );
+        }
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn run_arena(input: &str, stats: bool) {
+    let mut term = match pipeline::term(Pipeline::Typecheck, input) {
+        Ok(term) => term,
+        Err(err) => return println!("{}", err),
+    };
+    match arena::eval(&mut term) {
+        Ok(steps) => {
+            println!("{}", term);
+            if stats {
+                println!("beta steps: {}", steps);
+            }
+        }
+        Err(err) => println!("{}", err),
+    }
+}
+
+fn run_ski(input: &str, options: &ski::Options) {
+    let term = match pipeline::term(Pipeline::Typecheck, input) {
+        Ok(term) => term,
+        Err(err) => return println!("{}", err),
+    };
+    let combinators = match ski::translate(&term, options) {
+        Ok(combinators) => combinators,
+        Err(err) => return println!("{}", err),
+    };
+    println!("{}", combinators);
+    let (readback, steps) = match ski::reduce(&combinators) {
+        Ok(result) => result,
+        Err(err) => return println!("{}", err),
+    };
+    println!("{}", readback);
+    println!("reductions: {}", steps);
+    // `--bc` eta-reduces, so the results are compared up to beta-eta.
+    match eval::equivalent(&term, &readback) {
+        Ok(true) => println!("matches eval"),
+        Ok(false) => println!("does not match eval"),
         Err(err) => println!("{}", err),
-        _ => panic!(),
     }
 }
@@ -0,0 +1,309 @@
+use crate::construct::Term;
+use crate::define;
+use crate::eval;
+use crate::types::Type;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+const RECURSION_LIMIT: usize = 1000;
+
+/// Beta steps taken by the call-by-need and tree evaluators on the same term.
+#[derive(Debug, PartialEq)]
+pub struct Sharing {
+    pub need_steps: usize,
+    pub tree_steps: usize,
+}
+
+impl Sharing {
+    pub fn saved(&self) -> isize {
+        self.tree_steps as isize - self.need_steps as isize
+    }
+}
+
+#[derive(Clone)]
+enum Value<'a> {
+    Closure(char, usize, &'a Option<Type>, &'a Term, Env<'a>),
+    Neutral(Rc<Neutral<'a>>),
+}
+
+enum Neutral<'a> {
+    Var(char, usize),
+    App(Rc<Neutral<'a>>, Rc<Thunk<'a>>),
+    Def(char, usize, Rc<Thunk<'a>>),
+}
+
+struct Thunk<'a>(RefCell<State<'a>>);
+
+enum State<'a> {
+    Delayed(&'a Term, Env<'a>),
+    Forcing,
+    Done(Value<'a>),
+}
+
+type Env<'a> = Option<Rc<Scope<'a>>>;
+
+struct Scope<'a> {
+    id: usize,
+    thunk: Rc<Thunk<'a>>,
+    parent: Env<'a>,
+}
+
+struct Machine {
+    steps: usize,
+    recursion: usize,
+    bound: HashSet<usize>,
+    next_id: usize,
+}
+
+/// Reduces `term` to normal form with call-by-need, returning the beta steps.
+pub fn eval(term: &mut Term) -> Result<usize, String> {
+    let mut machine = Machine {
+        steps: 0,
+        recursion: 0,
+        bound: HashSet::new(),
+        next_id: define::next_id(term),
+    };
+    let value = machine.whnf(term, &None)?;
+    let normal = machine.readback(value)?;
+    *term = normal;
+    Ok(machine.steps)
+}
+
+/// Evaluates `term` with both evaluators, keeping the call-by-need result.
+pub fn compare(term: &mut Term) -> Result<Sharing, String> {
+    let tree_steps = eval::eval(&mut term.clone())?;
+    let need_steps = eval(term)?;
+    Ok(Sharing {
+        need_steps,
+        tree_steps,
+    })
+}
+
+fn bind<'a>(env: &Env<'a>, id: usize, thunk: Rc<Thunk<'a>>) -> Env<'a> {
+    Some(Rc::new(Scope {
+        id,
+        thunk,
+        parent: env.clone(),
+    }))
+}
+
+fn lookup<'a>(env: &Env<'a>, id: usize) -> Option<Rc<Thunk<'a>>> {
+    let mut env = env;
+    while let Some(scope) = env {
+        if scope.id == id {
+            return Some(scope.thunk.clone());
+        }
+        env = &scope.parent;
+    }
+    None
+}
+
+fn delay<'a>(term: &'a Term, env: &Env<'a>) -> Rc<Thunk<'a>> {
+    Rc::new(Thunk(RefCell::new(State::Delayed(term, env.clone()))))
+}
+
+fn done(value: Value) -> Rc<Thunk> {
+    Rc::new(Thunk(RefCell::new(State::Done(value))))
+}
+
+impl Machine {
+    fn whnf<'a>(&mut self, term: &'a Term, env: &Env<'a>) -> Result<Value<'a>, String> {
+        self.enter()?;
+        let value = match term {
+            Term::Var(name, id) => match lookup(env, define::id(*id)?) {
+                Some(thunk) => self.force(&thunk)?,
+                None => Value::Neutral(Rc::new(Neutral::Var(*name, define::id(*id)?))),
+            },
+            Term::Lambda(name, id, ty, body) => {
+                Value::Closure(*name, define::id(*id)?, ty, body, env.clone())
+            }
+            Term::App(func, arg) => {
+                let func = self.whnf(func, env)?;
+                self.apply(func, delay(arg, env))?
+            }
+            Term::Let(_, id, _, value, body) => self.let_in(*id, value, body, env)?,
+            Term::Def(name, id, body) => Value::Neutral(Rc::new(Neutral::Def(
+                *name,
+                define::id(*id)?,
+                delay(body, env),
+            ))),
+        };
+        self.recursion -= 1;
+        Ok(value)
+    }
+
+    fn apply<'a>(&mut self, func: Value<'a>, arg: Rc<Thunk<'a>>) -> Result<Value<'a>, String> {
+        match func {
+            Value::Closure(_, id, _, body, env) => {
+                self.steps += 1;
+                self.whnf(body, &bind(&env, id, arg))
+            }
+            Value::Neutral(neutral) => Ok(Value::Neutral(Rc::new(Neutral::App(neutral, arg)))),
+        }
+    }
+
+    fn let_in<'a>(
+        &mut self,
+        id: Option<usize>,
+        value: &'a Term,
+        body: &'a Term,
+        env: &Env<'a>,
+    ) -> Result<Value<'a>, String> {
+        self.steps += 1;
+        self.whnf(body, &bind(env, define::id(id)?, delay(value, env)))
+    }
+
+    fn force<'a>(&mut self, thunk: &Thunk<'a>) -> Result<Value<'a>, String> {
+        let state = thunk.0.replace(State::Forcing);
+        let value = match state {
+            State::Delayed(term, env) => self.whnf(term, &env)?,
+            State::Done(value) => value,
+            State::Forcing => return Err(String::from("Thunk depends on itself")),
+        };
+        thunk.0.replace(State::Done(value.clone()));
+        Ok(value)
+    }
+
+    fn readback(&mut self, value: Value) -> Result<Term, String> {
+        self.enter()?;
+        let term = match value {
+            Value::Closure(name, id, ty, body, env) => {
+                // Closures over the same lambda share its id, so only the
+                // first one read back keeps it.
+                let fresh = if self.bound.insert(id) {
+                    id
+                } else {
+                    let fresh = self.next_id;
+                    self.next_id += 1;
+                    fresh
+                };
+                let param = done(Value::Neutral(Rc::new(Neutral::Var(name, fresh))));
+                let body = self.whnf(body, &bind(&env, id, param))?;
+                Term::Lambda(
+                    name,
+                    Some(fresh),
+                    ty.clone(),
+                    Box::new(self.readback(body)?),
+                )
+            }
+            Value::Neutral(neutral) => self.readback_neutral(&neutral)?,
+        };
+        self.recursion -= 1;
+        Ok(term)
+    }
+
+    fn readback_neutral(&mut self, neutral: &Neutral) -> Result<Term, String> {
+        match neutral {
+            Neutral::Var(name, id) => Ok(Term::Var(*name, Some(*id))),
+            Neutral::App(func, arg) => {
+                let func = self.readback_neutral(func)?;
+                let arg = self.force(arg)?;
+                Ok(Term::App(Box::new(func), Box::new(self.readback(arg)?)))
+            }
+            Neutral::Def(name, id, body) => {
+                let body = self.force(body)?;
+                Ok(Term::Def(*name, Some(*id), Box::new(self.readback(body)?)))
+            }
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.recursion += 1;
+        if self.recursion >= RECURSION_LIMIT {
+            Err(String::from("Hit recursion limit"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::construct::app;
+    use crate::construct::lambda_id;
+    use crate::construct::var_id;
+    use crate::pipeline;
+    use crate::pipeline::Pipeline;
+    use crate::pipeline::PipelineResult;
+
+    macro_rules! run {
+        ($text:literal, $expected:expr) => {
+            if let Ok(PipelineResult::Term(mut term)) = pipeline::pipeline(Pipeline::Define, $text)
+            {
+                eval(&mut term).unwrap();
+                assert_eq!(term, $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    macro_rules! steps {
+        ($text:literal, $need:expr, $tree:expr) => {
+            if let Ok(PipelineResult::Term(mut term)) = pipeline::pipeline(Pipeline::Define, $text)
+            {
+                let expected = Sharing {
+                    need_steps: $need,
+                    tree_steps: $tree,
+                };
+                assert_eq!(compare(&mut term).unwrap(), expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    #[test]
+    fn test() {
+        run!("x", var_id('x', 0));
+        run!("(x -> x)", lambda_id('x', 0, var_id('x', 0)));
+        run!("(f x)", app(var_id('f', 0), var_id('x', 1)));
+        run!("((x -> x) y)", var_id('y', 1));
+        run!("((x -> (y -> x)) z)", lambda_id('y', 1, var_id('z', 2)));
+        run!("((a -> a) ((b -> c) d))", var_id('c', 2));
+        run!("(f ((x -> x) y))", app(var_id('f', 0), var_id('y', 2)));
+        run!("((f -> (f x)) (y -> y))", var_id('x', 1));
+        run!(
+            "((y -> (a -> a)) ((x -> (x x)) (x -> (x x))))",
+            lambda_id('a', 1, var_id('a', 1))
+        );
+        run!(
+            "(((x -> (y -> x)) (a -> a)) ((x -> (x x)) (x -> (x x))))",
+            lambda_id('a', 2, var_id('a', 2))
+        );
+        run!(
+            "((x -> (x x)) ((y -> y) (z -> z)))",
+            lambda_id('z', 2, var_id('z', 2))
+        );
+        run!(
+            "((s -> (s s)) (a -> (b -> (a b))))",
+            lambda_id(
+                'b',
+                2,
+                lambda_id('b', 3, app(var_id('b', 2), var_id('b', 3)))
+            )
+        );
+    }
+
+    #[test]
+    fn sharing() {
+        steps!("x", 0, 0);
+        steps!("((x -> x) y)", 1, 1);
+        steps!("((x -> (x x)) ((y -> y) (z -> z)))", 3, 4);
+        steps!("((x -> ((x x) x)) ((y -> y) (z -> z)))", 4, 6);
+        steps!("((y -> (a -> a)) ((x -> (x x)) (x -> (x x))))", 1, 1);
+    }
+
+    #[test]
+    fn limit() {
+        if let Ok(PipelineResult::Term(mut term)) =
+            pipeline::pipeline(Pipeline::Define, "((x -> (x x)) (x -> (x x)))")
+        {
+            assert_eq!(eval(&mut term), Err(String::from("Hit recursion limit")));
+        } else {
+            panic!();
+        }
+    }
+}
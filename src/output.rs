@@ -1,54 +1,617 @@
 use crate::construct::Term;
-use std::collections::HashMap;
+use crate::eval::Stats;
+use crate::json::Json;
+use crate::parse::Tree;
+use crate::sexp::Sexp;
+use crate::ski::Ski;
+use crate::systemf::FTerm;
+use crate::types::Type;
+use std::collections::HashSet;
 use std::fmt;
 
 impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        do_fmt(self, f, &mut HashMap::new())
+        let mut renderer = Renderer::new(self, &Ascii);
+        write!(f, "{}", renderer.render(self, Position::Body))
     }
 }
 
-fn do_fmt(
+/// A way of writing terms and types, given their rendered parts.
+pub trait Notation {
+    /// Whether lambdas and applications bring their own parentheses.
+    fn delimited(&self) -> bool {
+        false
+    }
+    /// Whether capturing binders are renamed rather than shown with ids.
+    fn renames(&self) -> bool {
+        false
+    }
+    fn name(&self, name: char, id: Option<usize>) -> String;
+    fn lambda(&self, param: &str, ty: Option<&str>, body: &str) -> String;
+    fn def(&self, name: &str, body: &str) -> String;
+    fn let_in(&self, name: &str, value: &str, body: &str) -> String;
+    fn app(&self, func: &str, arg: &str) -> String;
+    fn arrow(&self, param: &str, result: &str) -> String;
+    fn forall(&self, name: char, body: &str) -> String;
+}
+
+/// The input syntax, used by `Display`.
+pub struct Ascii;
+
+pub struct Unicode;
+
+pub struct Latex;
+
+impl Notation for Ascii {
+    fn delimited(&self) -> bool {
+        true
+    }
+
+    fn renames(&self) -> bool {
+        true
+    }
+
+    fn name(&self, name: char, id: Option<usize>) -> String {
+        match id {
+            Some(id) => format!("{}.{}", name, id),
+            None => name.to_string(),
+        }
+    }
+
+    fn lambda(&self, param: &str, ty: Option<&str>, body: &str) -> String {
+        match ty {
+            Some(ty) => format!("({} [{}] -> {})", param, ty, body),
+            None => format!("({} -> {})", param, body),
+        }
+    }
+
+    fn def(&self, name: &str, body: &str) -> String {
+        format!("({} : {})", name, body)
+    }
+
+    fn let_in(&self, name: &str, value: &str, body: &str) -> String {
+        format!("(let {} = {} in {})", name, value, body)
+    }
+
+    fn app(&self, func: &str, arg: &str) -> String {
+        format!("({} {})", func, arg)
+    }
+
+    fn arrow(&self, param: &str, result: &str) -> String {
+        format!("{} -> {}", param, result)
+    }
+
+    fn forall(&self, name: char, body: &str) -> String {
+        format!("[{}] -> {}", name, body)
+    }
+}
+
+impl Notation for Unicode {
+    fn name(&self, name: char, id: Option<usize>) -> String {
+        const SUBSCRIPTS: [char; 10] = ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+        let mut out = name.to_string();
+        if let Some(id) = id {
+            out.extend(
+                id.to_string()
+                    .bytes()
+                    .map(|digit| SUBSCRIPTS[(digit - b'0') as usize]),
+            );
+        }
+        out
+    }
+
+    fn lambda(&self, param: &str, ty: Option<&str>, body: &str) -> String {
+        match ty {
+            Some(ty) => format!("λ{}:{}.{}", param, ty, body),
+            None => format!("λ{}.{}", param, body),
+        }
+    }
+
+    fn def(&self, name: &str, body: &str) -> String {
+        format!("μ{}.{}", name, body)
+    }
+
+    fn let_in(&self, name: &str, value: &str, body: &str) -> String {
+        format!("let {} = {} in {}", name, value, body)
+    }
+
+    fn app(&self, func: &str, arg: &str) -> String {
+        format!("{} {}", func, arg)
+    }
+
+    fn arrow(&self, param: &str, result: &str) -> String {
+        format!("{} → {}", param, result)
+    }
+
+    fn forall(&self, name: char, body: &str) -> String {
+        format!("∀{}. {}", name, body)
+    }
+}
+
+impl Notation for Latex {
+    fn name(&self, name: char, id: Option<usize>) -> String {
+        match id {
+            Some(id) => format!("{}_{{{}}}", name, id),
+            None => name.to_string(),
+        }
+    }
+
+    fn lambda(&self, param: &str, ty: Option<&str>, body: &str) -> String {
+        match ty {
+            Some(ty) => format!("\\lambda {} : {}.\\, {}", param, ty, body),
+            None => format!("\\lambda {}.\\, {}", param, body),
+        }
+    }
+
+    fn def(&self, name: &str, body: &str) -> String {
+        format!("\\mu {}.\\, {}", name, body)
+    }
+
+    fn let_in(&self, name: &str, value: &str, body: &str) -> String {
+        format!(
+            "\\mathbf{{let}}\\ {} = {}\\ \\mathbf{{in}}\\ {}",
+            name, value, body
+        )
+    }
+
+    fn app(&self, func: &str, arg: &str) -> String {
+        format!("{}\\ {}", func, arg)
+    }
+
+    fn arrow(&self, param: &str, result: &str) -> String {
+        format!("{} \\to {}", param, result)
+    }
+
+    fn forall(&self, name: char, body: &str) -> String {
+        format!("\\forall {}.\\, {}", name, body)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Position {
+    Body,
+    Func,
+    Arg,
+}
+
+pub fn render(term: &Term, notation: &dyn Notation) -> Result<String, String> {
+    let mut renderer = Renderer::new(term, notation);
+    let out = renderer.render(term, Position::Body);
+    match renderer.error {
+        Some(err) => Err(err),
+        None => Ok(out),
+    }
+}
+
+/// Collects every name in `term` and the names of its free variables.
+fn collect_names(
     term: &Term,
-    f: &mut fmt::Formatter<'_>,
-    scope: &mut HashMap<char, usize>,
-) -> fmt::Result {
+    bound: &mut Vec<(char, Option<usize>)>,
+    names: &mut HashSet<char>,
+    free: &mut HashSet<char>,
+) {
     match term {
         Term::Var(name, id) => {
-            if let Some(parent_id) = scope.get(name) {
-                if id.unwrap() != *parent_id {
-                    return write!(f, "{}.{}", name, id.unwrap());
+            names.insert(*name);
+            if !bound.iter().any(|binder| binds(*binder, *name, *id)) {
+                free.insert(*name);
+            }
+        }
+        Term::Lambda(name, id, _, body) | Term::Def(name, id, body) => {
+            names.insert(*name);
+            bound.push((*name, *id));
+            collect_names(body, bound, names, free);
+            bound.pop();
+        }
+        Term::App(func, arg) => {
+            collect_names(func, bound, names, free);
+            collect_names(arg, bound, names, free);
+        }
+        Term::Let(name, id, _, value, body) => {
+            collect_names(value, bound, names, free);
+            names.insert(*name);
+            bound.push((*name, *id));
+            collect_names(body, bound, names, free);
+            bound.pop();
+        }
+    }
+}
+
+fn binds((name, id): (char, Option<usize>), var_name: char, var_id: Option<usize>) -> bool {
+    match (id, var_id) {
+        (Some(id), Some(var_id)) => id == var_id,
+        _ => name == var_name,
+    }
+}
+
+struct Renderer<'a> {
+    notation: &'a dyn Notation,
+    /// Enclosing binders with the name each was printed as.
+    scope: Vec<(char, Option<usize>, String)>,
+    names: HashSet<char>,
+    free: HashSet<char>,
+    error: Option<String>,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(term: &Term, notation: &'a dyn Notation) -> Renderer<'a> {
+        let mut names = HashSet::new();
+        let mut free = HashSet::new();
+        collect_names(term, &mut Vec::new(), &mut names, &mut free);
+        Renderer {
+            notation,
+            scope: Vec::new(),
+            names,
+            free,
+            error: None,
+        }
+    }
+
+    fn render(&mut self, term: &Term, position: Position) -> String {
+        let out = match term {
+            Term::Var(name, id) => self.var(*name, *id),
+            Term::Lambda(name, id, ty, body) => {
+                let param = self.bind(*name, *id, body);
+                let ty = ty.as_ref().map(|ty| render_type(ty, self.notation));
+                let body = self.render(body, Position::Body);
+                self.scope.pop();
+                self.notation.lambda(&param, ty.as_deref(), &body)
+            }
+            Term::Def(name, id, body) => {
+                let name = self.bind(*name, *id, body);
+                let body = self.render(body, Position::Body);
+                self.scope.pop();
+                self.notation.def(&name, &body)
+            }
+            Term::App(func, arg) => {
+                let func = self.render(func, Position::Func);
+                let arg = self.render(arg, Position::Arg);
+                self.notation.app(&func, &arg)
+            }
+            Term::Let(name, id, _, value, body) => {
+                let value = self.render(value, Position::Body);
+                let name = self.bind(*name, *id, body);
+                let body = self.render(body, Position::Body);
+                self.scope.pop();
+                self.notation.let_in(&name, &value, &body)
+            }
+        };
+        let parens = match term {
+            Term::Var(..) => false,
+            Term::Lambda(..) | Term::Def(..) | Term::Let(..) => position != Position::Body,
+            Term::App(..) => position == Position::Arg,
+        };
+        if parens && !self.notation.delimited() {
+            format!("({})", out)
+        } else {
+            out
+        }
+    }
+
+    fn var(&self, name: char, id: Option<usize>) -> String {
+        let binder = self
+            .scope
+            .iter()
+            .rev()
+            .find(|(binder, binder_id, _)| binds((*binder, *binder_id), name, id));
+        match binder {
+            Some((_, _, printed)) => printed.clone(),
+            None => self.notation.name(name, None),
+        }
+    }
+
+    /// Pushes a binder onto the scope, renaming it if it would capture.
+    fn bind(&mut self, name: char, id: Option<usize>, body: &Term) -> String {
+        let plain = self.notation.name(name, None);
+        let in_scope = self.scope.iter().any(|(_, _, printed)| *printed == plain);
+        let printed = if !in_scope && !self.free.contains(&name) {
+            plain
+        } else {
+            let mut outer = HashSet::new();
+            self.outer_names(body, &mut vec![(name, id)], &mut outer);
+            if !outer.contains(&plain) {
+                plain
+            } else if !self.notation.renames() {
+                self.notation.name(name, id)
+            } else {
+                let unused = |letter: &char| !outer.contains(&self.notation.name(*letter, None));
+                let fresh = ('a'..='z')
+                    .filter(unused)
+                    .find(|letter| !self.names.contains(letter))
+                    .or_else(|| ('a'..='z').find(unused));
+                match fresh {
+                    Some(fresh) => {
+                        self.names.insert(fresh);
+                        self.notation.name(fresh, None)
+                    }
+                    None => {
+                        self.error.get_or_insert_with(|| {
+                            format!("Cannot rename `{}` without capturing a variable", name)
+                        });
+                        self.notation.name(name, id)
+                    }
+                }
+            }
+        };
+        self.scope.push((name, id, printed.clone()));
+        printed
+    }
+
+    /// Collects the printed names of variables of `term` not bound in `bound`.
+    fn outer_names(
+        &self,
+        term: &Term,
+        bound: &mut Vec<(char, Option<usize>)>,
+        names: &mut HashSet<String>,
+    ) {
+        match term {
+            Term::Var(name, id) => {
+                if !bound.iter().any(|binder| binds(*binder, *name, *id)) {
+                    names.insert(self.var(*name, *id));
+                }
+            }
+            Term::Lambda(name, id, _, body) | Term::Def(name, id, body) => {
+                bound.push((*name, *id));
+                self.outer_names(body, bound, names);
+                bound.pop();
+            }
+            Term::App(func, arg) => {
+                self.outer_names(func, bound, names);
+                self.outer_names(arg, bound, names);
+            }
+            Term::Let(name, id, _, value, body) => {
+                self.outer_names(value, bound, names);
+                bound.push((*name, *id));
+                self.outer_names(body, bound, names);
+                bound.pop();
+            }
+        }
+    }
+}
+
+pub fn render_type(ty: &Type, notation: &dyn Notation) -> String {
+    match ty {
+        Type::Var(name) => name.to_string(),
+        Type::Arrow(param, result) => {
+            let result = render_type(result, notation);
+            match **param {
+                Type::Arrow(..) | Type::Forall(..) => {
+                    notation.arrow(&format!("({})", render_type(param, notation)), &result)
                 }
+                Type::Var(_) => notation.arrow(&render_type(param, notation), &result),
+            }
+        }
+        Type::Forall(name, body) => notation.forall(*name, &render_type(body, notation)),
+    }
+}
+
+/// Prints a parsed program in canonical layout, breaking what overflows `width`.
+pub fn layout(tree: &Tree, width: usize) -> String {
+    let mut out = String::new();
+    layout_tree(tree, 0, 0, width, &mut out);
+    out.push('\n');
+    out
+}
+
+fn flat(tree: &Tree) -> String {
+    match tree {
+        Tree::Arrow => String::from("->"),
+        Tree::Colon => String::from(":"),
+        Tree::Assert => String::from("assert"),
+        Tree::Equals => String::from("=="),
+        Tree::Let => String::from("let"),
+        Tree::Rec => String::from("rec"),
+        Tree::In => String::from("in"),
+        Tree::Where => String::from("where"),
+        Tree::Bind => String::from("="),
+        Tree::Name(name) => name.to_string(),
+        Tree::Branch(items) => format!("({})", flat_items(items)),
+        Tree::Bracket(items) => format!("[{}]", flat_items(items)),
+    }
+}
+
+fn flat_items(items: &[Tree]) -> String {
+    items.iter().map(flat).collect::<Vec<_>>().join(" ")
+}
+
+/// Lays out `tree` at `column`, followed by `trail` characters.
+fn layout_tree(tree: &Tree, column: usize, trail: usize, width: usize, out: &mut String) {
+    let text = flat(tree);
+    let items = match tree {
+        Tree::Branch(items) if column + text.chars().count() + trail > width => items,
+        _ => return out.push_str(&text),
+    };
+    let header = items
+        .iter()
+        .position(|item| matches!(item, Tree::Arrow | Tree::Colon))
+        .filter(|&position| position + 2 == items.len());
+    if items
+        .iter()
+        .any(|item| matches!(item, Tree::In | Tree::Where))
+    {
+        let mut lines: Vec<&[Tree]> = Vec::new();
+        let mut start = 0;
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 && matches!(item, Tree::In | Tree::Where) {
+                lines.push(&items[start..index]);
+                start = index;
             }
-            write!(f, "{}", name)
         }
-        Term::Lambda(name, id, term) => {
-            if scope.get(name).is_some() {
-                write!(f, "({}.{} -> ", name, id.unwrap())?;
-                do_fmt(term, f, scope)?;
+        lines.push(&items[start..]);
+        out.push('(');
+        for (index, line) in lines.iter().enumerate() {
+            if index > 0 {
+                out.push('\n');
+                out.push_str(&" ".repeat(column + 1));
+            }
+            let trail = if index + 1 == lines.len() {
+                trail + 1
             } else {
-                scope.insert(*name, id.unwrap());
-                write!(f, "({} -> ", name)?;
-                do_fmt(term, f, scope)?;
-                scope.remove(name);
+                0
+            };
+            let (last, init) = match line.split_last() {
+                Some(split) => split,
+                None => continue,
+            };
+            let mut prefix = flat_items(init);
+            if !prefix.is_empty() {
+                prefix.push(' ');
+            }
+            out.push_str(&prefix);
+            layout_tree(last, column + 1 + prefix.chars().count(), trail, width, out);
+        }
+        out.push(')');
+        return;
+    }
+    match header {
+        Some(position) => {
+            out.push('(');
+            out.push_str(&flat_items(&items[..=position]));
+            out.push('\n');
+            out.push_str(&" ".repeat(column + 2));
+            layout_tree(&items[position + 1], column + 2, trail + 1, width, out);
+        }
+        None => {
+            out.push('(');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push('\n');
+                    out.push_str(&" ".repeat(column + 1));
+                }
+                let trail = if index + 1 == items.len() {
+                    trail + 1
+                } else {
+                    0
+                };
+                layout_tree(item, column + 1, trail, width, out);
+            }
+        }
+    }
+    out.push(')');
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(f, "{}", *number as i64)
+            }
+            Json::Number(number) => write!(f, "{}", number),
+            Json::String(string) => write_json_string(f, string),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_json_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
             }
-            write!(f, ")")
         }
-        Term::App(term1, term2) => {
-            write!(f, "(")?;
-            do_fmt(term1, f, scope)?;
-            write!(f, " ")?;
-            do_fmt(term2, f, scope)?;
-            write!(f, ")")
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in string.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            ch if (ch as u32) < 0x20 => write!(f, "\\u{:04x}", ch as u32)?,
+            ch => write!(f, "{}", ch)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Sexp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sexp::Atom(atom) => write!(f, "{}", atom),
+            Sexp::List(items) => {
+                write!(f, "(")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
 
+impl fmt::Display for Ski {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ski::S => write!(f, "S"),
+            Ski::K => write!(f, "K"),
+            Ski::I => write!(f, "I"),
+            Ski::B => write!(f, "B"),
+            Ski::C => write!(f, "C"),
+            Ski::Var(name, _) => write!(f, "{}", name),
+            Ski::App(func, arg) => write!(f, "({} {})", func, arg),
+        }
+    }
+}
+
+impl fmt::Display for FTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FTerm::Var(name) => write!(f, "{}", name),
+            FTerm::Lambda(name, ty, body) => write!(f, "({} [{}] -> {})", name, ty, body),
+            FTerm::App(func, arg) => write!(f, "({} {})", func, arg),
+            FTerm::TypeLambda(name, body) => write!(f, "([{}] -> {})", name, body),
+            FTerm::TypeApp(func, ty) => write!(f, "({} [{}])", func, ty),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", render_type(self, &Ascii))
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "beta steps: {}", self.beta_steps)?;
+        writeln!(f, "eta steps: {}", self.eta_steps)?;
+        writeln!(f, "max size: {}", self.max_size)?;
+        writeln!(f, "max depth: {}", self.max_depth)?;
+        writeln!(f, "substitutions: {}", self.substitutions)?;
+        write!(f, "elapsed: {:?}", self.elapsed)
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::construct;
+    use crate::debruijn;
     use crate::pipeline;
+    use crate::pipeline::define;
     use crate::pipeline::Pipeline;
     use crate::pipeline::PipelineResult;
+    use crate::random;
 
     macro_rules! run {
         ($text:literal, $expected:literal) => {
@@ -200,4 +763,196 @@ mod test {
         // (snd (pair a b)) = b
         run!("((p -> (p (x -> (y -> y)))) (p -> ((p a) b)))", "b");
     }
+
+    macro_rules! notation {
+        ($text:literal, $unicode:literal, $latex:literal) => {
+            if let Ok(PipelineResult::Term(term)) = pipeline::pipeline(Pipeline::Define, $text) {
+                assert_eq!(render(&term, &Unicode), Ok(String::from($unicode)));
+                assert_eq!(render(&term, &Latex), Ok(String::from($latex)));
+                assert_eq!(render(&term, &Ascii), Ok(format!("{}", term)));
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    #[test]
+    fn notations() {
+        notation!(
+            "(f -> (x -> (f (f x))))",
+            "λf.λx.f (f x)",
+            "\\lambda f.\\, \\lambda x.\\, f\\ (f\\ x)"
+        );
+        notation!(
+            "(((x -> x) (y -> y)) (a b))",
+            "(λx.x) (λy.y) (a b)",
+            "(\\lambda x.\\, x)\\ (\\lambda y.\\, y)\\ (a\\ b)"
+        );
+        notation!(
+            "(x -> (x -> x))",
+            "λx.λx.x",
+            "\\lambda x.\\, \\lambda x.\\, x"
+        );
+        notation!(
+            "(x -> (y -> ((x -> (x y)) x)))",
+            "λx.λy.(λx.x y) x",
+            "\\lambda x.\\, \\lambda y.\\, (\\lambda x.\\, x\\ y)\\ x"
+        );
+        notation!(
+            "(f [(a -> b) -> a] -> f)",
+            "λf:(a → b) → a.f",
+            "\\lambda f : (a \\to b) \\to a.\\, f"
+        );
+        notation!(
+            "(f : (x -> (f x)))",
+            "μf.λx.f x",
+            "\\mu f.\\, \\lambda x.\\, f\\ x"
+        );
+    }
+
+    /// Every term of the given size over the names `x` and `y`.
+    fn terms(size: usize) -> Vec<String> {
+        let mut all = Vec::new();
+        if size == 1 {
+            all.push(String::from("x"));
+            all.push(String::from("y"));
+        }
+        if size >= 2 {
+            for body in terms(size - 1) {
+                all.push(format!("(x -> {})", body));
+                all.push(format!("(y -> {})", body));
+            }
+        }
+        for left in 1..size.saturating_sub(1) {
+            for func in terms(left) {
+                for arg in terms(size - 1 - left) {
+                    all.push(format!("({} {})", func, arg));
+                }
+            }
+        }
+        all
+    }
+
+    fn round_trip(term: &Term) {
+        let printed = format!("{}", term);
+        assert!(
+            debruijn::alpha_equivalent(&define(&printed), term),
+            "{}",
+            printed
+        );
+    }
+
+    #[test]
+    fn shadowing() {
+        let mut term = define("((y -> (x -> y)) x)");
+        crate::eval::eval(&mut term).unwrap();
+        assert_eq!(format!("{}", term), "(a -> x)");
+        assert_eq!(format!("{}", define("(x -> (x -> x))")), "(x -> (x -> x))");
+        assert_eq!(
+            format!("{}", define("(x -> ((x -> x) x))")),
+            "(x -> ((x -> x) x))"
+        );
+        let mut term = define("((y -> (x -> (x -> y))) x)");
+        crate::eval::eval(&mut term).unwrap();
+        assert_eq!(format!("{}", term), "(a -> (b -> x))");
+        let mut term = define("((y -> (z -> (x -> (z y)))) x)");
+        crate::eval::eval(&mut term).unwrap();
+        assert_eq!(format!("{}", term), "(z -> (a -> (z x)))");
+
+        let free = ('a'..='z').fold(construct::var_id('i', 0), |term, name| {
+            construct::app(term, construct::var_id(name, name as usize))
+        });
+        let term = construct::lambda_id('x', 0, free);
+        assert_eq!(
+            render(&term, &Ascii),
+            Err(String::from(
+                "Cannot rename `x` without capturing a variable"
+            ))
+        );
+        // `Display` can't fail, so it tells the binder apart by its id.
+        let body = ('a'..='z').fold(String::from("x.0"), |out, name| {
+            format!("({} {})", out, name)
+        });
+        assert_eq!(format!("{}", term), format!("(x.0 -> {})", body));
+
+        for size in 1..=6 {
+            for text in terms(size) {
+                let mut term = define(&text);
+                round_trip(&term);
+                if crate::eval::eval(&mut term).is_ok() {
+                    round_trip(&term);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_round_trip() {
+        let limit = crate::eval::Options {
+            limit: Some(100),
+            ..crate::eval::Options::default()
+        };
+        for seed in 0..500 {
+            let options = random::Options {
+                names: 4,
+                closed: seed % 2 == 0,
+                ..random::Options::default()
+            };
+            let mut term = random::term(&mut random::Rng::new(seed), &options);
+            crate::define::define(&mut term);
+            round_trip(&term);
+            if crate::eval::eval_with(&mut term, &limit).is_ok() {
+                round_trip(&term);
+            }
+        }
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(
+            pipeline::format("(x->( f   x ))", 80),
+            Ok(String::from("(x -> (f x))\n"))
+        );
+        assert_eq!(
+            pipeline::format("(f:(x [a->b] ->(f x)))", 80),
+            Ok(String::from("(f : (x [a -> b] -> (f x)))\n"))
+        );
+        let formatted = pipeline::format(
+            "((n -> (f -> (x -> (f ((n f) x))))) (f -> (x -> (f (f (f x))))))",
+            30,
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            "((n ->
+   (f ->
+     (x -> (f ((n f) x)))))
+ (f -> (x -> (f (f (f x))))))
+"
+        );
+        assert_eq!(pipeline::format(&formatted, 30), Ok(formatted));
+        assert_eq!(
+            pipeline::format("(f -> (x -> (f (f (f (f x))))))", 20),
+            Ok(String::from(
+                "(f ->
+  (x ->
+    (f
+     (f
+      (f (f x))))))
+"
+            ))
+        );
+        assert_eq!(
+            pipeline::format("(let i = (x -> x) in ((i i) where j = (i i)))", 30),
+            Ok(String::from(
+                "(let i = (x -> x)
+ in ((i i) where j = (i i)))
+"
+            ))
+        );
+        assert_eq!(
+            pipeline::format("(assert x == x)\n(f  x)\n\n(x->x)", 80),
+            Ok(String::from("(assert x == x)\n(f x)\n(x -> x)\n"))
+        );
+    }
 }
@@ -1,88 +1,304 @@
 use crate::lex::Token;
-use crate::lex::TokenIter;
+use crate::source::SourceError;
+use crate::source::Span;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Tree {
     Arrow,
     Colon,
+    Assert,
+    Equals,
+    Let,
+    Rec,
+    In,
+    Where,
+    Bind,
     Name(char),
     Branch(Vec<Tree>),
+    Bracket(Vec<Tree>),
 }
 
-pub fn parse(tokens: &[Token]) -> Result<Tree, String> {
-    match do_parse(tokens.iter().peekable(), None) {
-        Ok((_, tree)) => Ok(tree),
-        Err(s) => Err(s),
-    }
-}
+/// Deepest nesting accepted, so later stages don't overflow the stack.
+pub const NESTING_LIMIT: usize = 256;
 
-pub fn do_parse(
-    mut tokens: TokenIter,
-    mut tree: Option<Tree>,
-) -> Result<(TokenIter, Tree), String> {
-    match tokens.next() {
-        Some(token) => match token {
-            Token::Name(ch) => match &mut tree {
-                None => Ok(do_parse(tokens, Some(Tree::Name(*ch)))?),
-                Some(Tree::Branch(branch)) => {
-                    branch.push(Tree::Name(*ch));
-                    Ok(do_parse(tokens, tree)?)
-                }
-                Some(Tree::Name(_)) | Some(Tree::Arrow) | Some(Tree::Colon) => {
-                    Err(String::from("Missing parentheses"))
-                }
-            },
-            Token::Arrow => match &mut tree {
-                None => Err(String::from("Missing lambda parameter before arrow")),
-                Some(Tree::Branch(branch)) => {
-                    branch.push(Tree::Arrow);
-                    Ok(do_parse(tokens, tree)?)
+const TOO_DEEP: &str = "Too deeply nested";
+
+pub fn parse(tokens: &[Token]) -> Result<Tree, String> {
+    // A whole program is a single term, but as long as that term is a branch
+    // any following terms are taken to be its items.
+    let mut top: Option<Tree> = None;
+    let mut open: Vec<Tree> = Vec::new();
+    for token in tokens {
+        let leaf = match token {
+            Token::Name(ch) => Tree::Name(*ch),
+            Token::Arrow => Tree::Arrow,
+            Token::Colon => Tree::Colon,
+            Token::Assert => Tree::Assert,
+            Token::Equals => Tree::Equals,
+            Token::Let => Tree::Let,
+            Token::Rec => Tree::Rec,
+            Token::In => Tree::In,
+            Token::Where => Tree::Where,
+            Token::Bind => Tree::Bind,
+            Token::Open | Token::OpenBracket => {
+                if let Some(Tree::Name(_)) = top {
+                    return Err(String::from("Unexpected parenthesis"));
                 }
-                Some(Tree::Name(_)) | Some(Tree::Arrow) | Some(Tree::Colon) => {
-                    Err(String::from("Missing parentheses"))
+                if open.len() == NESTING_LIMIT {
+                    return Err(String::from(TOO_DEEP));
                 }
+                open.push(if let Token::Open = token {
+                    Tree::Branch(Vec::new())
+                } else {
+                    Tree::Bracket(Vec::new())
+                });
+                continue;
+            }
+            Token::Close | Token::CloseBracket => {
+                let tree = match open.pop() {
+                    Some(tree) => tree,
+                    None => match top.take() {
+                        None if *token == Token::Close => {
+                            return Err(String::from(
+                                "Closing parenthesis is invalid at start of program",
+                            ))
+                        }
+                        None => {
+                            return Err(String::from(
+                                "Closing bracket is invalid at start of program",
+                            ))
+                        }
+                        Some(tree) => {
+                            close(&tree, *token)?;
+                            return Ok(tree);
+                        }
+                    },
+                };
+                close(&tree, *token)?;
+                add(&mut top, &mut open, tree);
+                continue;
+            }
+        };
+        match (open.last_mut(), &mut top) {
+            (Some(Tree::Branch(branch)), _)
+            | (Some(Tree::Bracket(branch)), _)
+            | (None, Some(Tree::Branch(branch)))
+            | (None, Some(Tree::Bracket(branch))) => branch.push(leaf),
+            (None, None) => match leaf {
+                Tree::Name(_) => top = Some(leaf),
+                _ => return Err(String::from(misplaced(*token))),
             },
-            Token::Colon => match &mut tree {
-                None => Err(String::from("Missing name before colon")),
-                Some(Tree::Branch(branch)) => {
-                    branch.push(Tree::Colon);
-                    Ok(do_parse(tokens, tree)?)
+            _ => return Err(String::from("Missing parentheses")),
+        }
+    }
+    while let Some(tree) = open.pop() {
+        add(&mut top, &mut open, tree);
+    }
+    top.ok_or_else(|| String::from("Empty program!"))
+}
+
+/// The error for a token that can't start a program.
+fn misplaced(token: Token) -> &'static str {
+    match token {
+        Token::Arrow => "Missing lambda parameter before arrow",
+        Token::Colon => "Missing name before colon",
+        Token::Assert => "Missing parentheses around assertion",
+        Token::Equals => "Missing term before '=='",
+        Token::Let => "Missing parentheses around `let`",
+        Token::Rec => "Missing `let` or `where` before `rec`",
+        Token::In => "Missing `let` before `in`",
+        Token::Where => "Missing term before `where`",
+        Token::Bind => "Missing name before '='",
+        Token::Name(_) | Token::Open | Token::Close | Token::OpenBracket | Token::CloseBracket => {
+            "Unexpected token"
+        }
+    }
+}
+
+/// Checks that `tree` may be closed by `token`.
+fn close(tree: &Tree, token: Token) -> Result<(), String> {
+    match (tree, token) {
+        (Tree::Bracket(_), Token::Close) => Err(String::from("Expected ']' before ')'")),
+        (Tree::Bracket(_), _) | (_, Token::Close) => Ok(()),
+        _ => Err(String::from("Expected ')' before ']'")),
+    }
+}
+
+/// Adds a closed tree to the innermost open one, or to the top level.
+fn add(top: &mut Option<Tree>, open: &mut [Tree], tree: Tree) {
+    match (open.last_mut(), top) {
+        (Some(Tree::Branch(branch)), _)
+        | (Some(Tree::Bracket(branch)), _)
+        | (None, Some(Tree::Branch(branch)))
+        | (None, Some(Tree::Bracket(branch))) => branch.push(tree),
+        (_, top) => *top = Some(tree),
+    }
+}
+
+/// Spans of the nodes of the tree parsed from `tokens`, in preorder.
+pub fn spans(tokens: &[(Token, Span)]) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut open = Vec::new();
+    for (token, span) in tokens {
+        match token {
+            Token::Close | Token::CloseBracket => {
+                if let Some(index) = open.pop() {
+                    let branch: &mut Span = &mut spans[index];
+                    branch.end = span.end;
                 }
-                Some(Tree::Name(_)) | Some(Tree::Arrow) | Some(Tree::Colon) => {
-                    Err(String::from("Missing parentheses"))
+            }
+            Token::Open | Token::OpenBracket => {
+                open.push(spans.len());
+                spans.push(*span);
+            }
+            Token::Name(_)
+            | Token::Arrow
+            | Token::Colon
+            | Token::Assert
+            | Token::Equals
+            | Token::Let
+            | Token::Rec
+            | Token::In
+            | Token::Where
+            | Token::Bind => spans.push(*span),
+        }
+    }
+    spans
+}
+
+/// A tree with the span of each of its nodes in preorder.
+pub type Spanned = (Tree, Vec<Span>);
+
+struct Frame {
+    bracket: bool,
+    open: Span,
+    items: Vec<Spanned>,
+}
+
+impl Frame {
+    fn is_definition(&self) -> bool {
+        matches!(
+            self.items.as_slice(),
+            [(Tree::Name(_), _), (Tree::Colon, _), ..]
+        )
+    }
+
+    fn close(self, end: usize) -> Spanned {
+        let mut trees = Vec::new();
+        let mut spans = vec![Span {
+            start: self.open.start,
+            end,
+        }];
+        for (tree, item_spans) in self.items {
+            trees.push(tree);
+            spans.extend(item_spans);
+        }
+        if self.bracket {
+            (Tree::Bracket(trees), spans)
+        } else {
+            (Tree::Branch(trees), spans)
+        }
+    }
+}
+
+/// Parses a sequence of terms, recovering from errors to report them all.
+pub fn parse_recovering(tokens: &[(Token, Span)]) -> (Vec<Spanned>, Vec<SourceError>) {
+    let mut terms = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut errors = Vec::new();
+    let push =
+        |stack: &mut Vec<Frame>, terms: &mut Vec<Spanned>, item: Spanned| match stack.last_mut() {
+            Some(frame) => frame.items.push(item),
+            None => terms.push(item),
+        };
+    let mut end = 0;
+
+    for (position, (token, span)) in tokens.iter().enumerate() {
+        match token {
+            Token::Name(ch) => push(&mut stack, &mut terms, (Tree::Name(*ch), vec![*span])),
+            Token::Arrow
+            | Token::Colon
+            | Token::Assert
+            | Token::Equals
+            | Token::Let
+            | Token::Rec
+            | Token::In
+            | Token::Where
+            | Token::Bind
+                if stack.is_empty() =>
+            {
+                errors.push((*span, String::from(misplaced(*token))));
+            }
+            Token::Arrow => push(&mut stack, &mut terms, (Tree::Arrow, vec![*span])),
+            Token::Colon => push(&mut stack, &mut terms, (Tree::Colon, vec![*span])),
+            Token::Assert => push(&mut stack, &mut terms, (Tree::Assert, vec![*span])),
+            Token::Equals => push(&mut stack, &mut terms, (Tree::Equals, vec![*span])),
+            Token::Let => push(&mut stack, &mut terms, (Tree::Let, vec![*span])),
+            Token::Rec => push(&mut stack, &mut terms, (Tree::Rec, vec![*span])),
+            Token::In => push(&mut stack, &mut terms, (Tree::In, vec![*span])),
+            Token::Where => push(&mut stack, &mut terms, (Tree::Where, vec![*span])),
+            Token::Bind => push(&mut stack, &mut terms, (Tree::Bind, vec![*span])),
+            Token::Open | Token::OpenBracket => {
+                if stack.len() == NESTING_LIMIT {
+                    errors.push((*span, String::from(TOO_DEEP)));
+                    return (terms, errors);
                 }
-            },
-            Token::Open => match &mut tree {
-                None => {
-                    let (tokens, new_tree) = do_parse(tokens, Some(Tree::Branch(Vec::new())))?;
-                    Ok(do_parse(tokens, Some(new_tree))?)
+                let definition = matches!(
+                    tokens.get(position + 1..position + 3),
+                    Some([(Token::Name(_), _), (Token::Colon, _)])
+                );
+                if *token == Token::Open && definition && stack.iter().any(Frame::is_definition) {
+                    while let Some(frame) = stack.pop() {
+                        errors.push((frame.open, String::from("Unclosed parenthesis")));
+                        push(&mut stack, &mut terms, frame.close(end));
+                    }
                 }
-                Some(Tree::Branch(branch)) => {
-                    let (tokens, new_tree) = do_parse(tokens, Some(Tree::Branch(Vec::new())))?;
-                    branch.push(new_tree);
-                    Ok(do_parse(tokens, tree)?)
+                stack.push(Frame {
+                    bracket: *token == Token::OpenBracket,
+                    open: *span,
+                    items: Vec::new(),
+                });
+            }
+            Token::Close | Token::CloseBracket => {
+                let bracket = *token == Token::CloseBracket;
+                let (close, other) = if bracket { (']', ')') } else { (')', ']') };
+                if !stack.iter().any(|frame| frame.bracket == bracket) {
+                    errors.push((*span, format!("Unmatched '{}'", close)));
+                    continue;
                 }
-                Some(Tree::Name(_)) | Some(Tree::Arrow) | Some(Tree::Colon) => {
-                    Err(String::from("Unexpected parenthesis"))
+                while let Some(frame) = stack.pop() {
+                    if frame.bracket == bracket {
+                        push(&mut stack, &mut terms, frame.close(span.end));
+                        break;
+                    }
+                    errors.push((*span, format!("Expected '{}' before '{}'", other, close)));
+                    push(&mut stack, &mut terms, frame.close(span.start));
                 }
-            },
-            Token::Close => match tree {
-                None => Err(String::from(
-                    "Closing parenthesis is invalid at start of program",
-                )),
-                Some(tree) => Ok((tokens, tree)),
-            },
-        },
-        None => match tree {
-            None => Err(String::from("Empty program!")),
-            Some(tree) => Ok((tokens, tree)),
-        },
+            }
+        }
+        end = span.end;
+    }
+
+    while let Some(frame) = stack.pop() {
+        let err = if frame.bracket {
+            "Unclosed bracket"
+        } else {
+            "Unclosed parenthesis"
+        };
+        errors.push((frame.open, String::from(err)));
+        push(&mut stack, &mut terms, frame.close(end));
+    }
+    if tokens.is_empty() {
+        errors.push((Span::default(), String::from("Empty program!")));
     }
+    errors.sort_by_key(|(span, _)| span.start);
+    (terms, errors)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::lex;
     use crate::pipeline;
     use crate::pipeline::Pipeline;
     use crate::pipeline::PipelineResult;
@@ -120,6 +336,15 @@ mod test {
             "(x -> y)",
             Tree::Branch(vec![Tree::Name('x'), Tree::Arrow, Tree::Name('y')])
         );
+        ok!(
+            "(x [a -> b] -> x)",
+            Tree::Branch(vec![
+                Tree::Name('x'),
+                Tree::Bracket(vec![Tree::Name('a'), Tree::Arrow, Tree::Name('b')]),
+                Tree::Arrow,
+                Tree::Name('x')
+            ])
+        );
         err!("", String::from("Empty program!"));
         err!("->", String::from("Missing lambda parameter before arrow"));
         err!(
@@ -133,5 +358,69 @@ mod test {
             ")",
             String::from("Closing parenthesis is invalid at start of program")
         );
+        err!("(x [a) -> x)", String::from("Expected ']' before ')'"));
+        err!("(x ] -> x)", String::from("Expected ')' before ']'"));
+        err!(
+            "let x = y in x",
+            String::from("Missing parentheses around `let`")
+        );
+        err!("x = y", String::from("Missing parentheses"));
+
+        let nested = |depth| format!("{}x{}", "(x -> ".repeat(depth), ")".repeat(depth));
+        assert!(pipeline::pipeline(Pipeline::Parse, &nested(NESTING_LIMIT)).is_ok());
+        assert_eq!(
+            pipeline::pipeline(Pipeline::Parse, &nested(NESTING_LIMIT + 1)),
+            Err(String::from("Too deeply nested"))
+        );
+    }
+
+    fn recover(text: &str) -> (Vec<Tree>, Vec<(usize, String)>) {
+        let (trees, errors) = parse_recovering(&lex::lex_spanned(text).unwrap());
+        (
+            trees.into_iter().map(|(tree, _)| tree).collect(),
+            errors
+                .into_iter()
+                .map(|(span, err)| (span.start, err))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn recovering() {
+        let (trees, errors) = recover("(x -> x)");
+        assert_eq!(
+            trees,
+            vec![Tree::Branch(vec![
+                Tree::Name('x'),
+                Tree::Arrow,
+                Tree::Name('x')
+            ])]
+        );
+        assert_eq!(errors, vec![]);
+
+        let (trees, errors) = recover("(x -> x)) -> (y [a) -> (y");
+        assert_eq!(trees.len(), 3);
+        assert_eq!(
+            errors,
+            vec![
+                (8, String::from("Unmatched ')'")),
+                (10, String::from("Missing lambda parameter before arrow")),
+                (18, String::from("Expected ']' before ')'")),
+                (20, String::from("Missing lambda parameter before arrow")),
+                (23, String::from("Unclosed parenthesis")),
+            ]
+        );
+
+        let (trees, errors) = recover("(f : (x -> x)\n(g : f)");
+        assert_eq!(trees.len(), 2);
+        assert_eq!(errors, vec![(0, String::from("Unclosed parenthesis"))]);
+        let (trees, errors) = recover("((f : x) (g : x))");
+        assert_eq!(trees.len(), 1);
+        assert_eq!(errors, vec![]);
+
+        let (_, spans) = &parse_recovering(&lex::lex_spanned("(x\n(y z").unwrap()).0[0];
+        let spans: Vec<_> = spans.iter().map(|span| (span.start, span.end)).collect();
+        assert_eq!(spans, vec![(0, 7), (1, 2), (3, 7), (4, 5), (6, 7)]);
+        assert_eq!(recover("").1, vec![(0, String::from("Empty program!"))]);
     }
 }
@@ -1,19 +1,32 @@
 use crate::construct;
+use crate::construct::Fragment;
 use crate::construct::Term;
 use crate::define;
 use crate::eval;
+use crate::free;
+use crate::infer;
 use crate::lex;
 use crate::lex::Token;
+use crate::lint;
+use crate::lint::Severity;
+use crate::output;
 use crate::parse;
 use crate::parse::Tree;
+use crate::source;
+use crate::source::SourceError;
+use crate::source::Span;
+use crate::systemf;
+use crate::typecheck;
+use crate::types::Type;
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Pipeline {
     Lex,
     Parse,
     Construct,
     Define,
+    Typecheck,
     Eval,
 }
 
@@ -25,7 +38,8 @@ pub enum PipelineResult {
 }
 
 pub fn pipeline(pipeline: Pipeline, input: &str) -> Result<PipelineResult, String> {
-    let lexed = lex::lex(input)?;
+    let spanned = lex::lex_spanned(input)?;
+    let lexed: Vec<Token> = spanned.iter().map(|(token, _)| *token).collect();
     if let Pipeline::Lex = pipeline {
         return Ok(PipelineResult::Tokens(lexed));
     }
@@ -35,7 +49,7 @@ pub fn pipeline(pipeline: Pipeline, input: &str) -> Result<PipelineResult, Strin
         return Ok(PipelineResult::Tree(parsed));
     }
 
-    let mut constructed = construct::construct(&parsed)?;
+    let (mut constructed, spans) = construct::construct_located(&parsed, &parse::spans(&spanned))?;
     if let Pipeline::Construct = pipeline {
         return Ok(PipelineResult::Term(constructed));
     }
@@ -45,6 +59,13 @@ pub fn pipeline(pipeline: Pipeline, input: &str) -> Result<PipelineResult, Strin
         return Ok(PipelineResult::Term(constructed));
     }
 
+    if let Err((index, err)) = typecheck::typecheck(&constructed) {
+        return Err(source::locate(input, spans[index], &err));
+    }
+    if let Pipeline::Typecheck = pipeline {
+        return Ok(PipelineResult::Term(constructed));
+    }
+
     eval::eval(&mut constructed)?;
     if let Pipeline::Eval = pipeline {
         return Ok(PipelineResult::Term(constructed));
@@ -52,3 +73,117 @@ pub fn pipeline(pipeline: Pipeline, input: &str) -> Result<PipelineResult, Strin
 
     Ok(PipelineResult::Term(constructed))
 }
+
+/// Like `pipeline` for the stages that produce a term.
+pub fn term(pipeline: Pipeline, input: &str) -> Result<Term, String> {
+    match self::pipeline(pipeline, input)? {
+        PipelineResult::Term(term) => Ok(term),
+        PipelineResult::Tokens(_) | PipelineResult::Tree(_) => {
+            Err(String::from("Stage does not produce a term"))
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn define(input: &str) -> Term {
+    match term(Pipeline::Define, input) {
+        Ok(term) => term,
+        Err(error) => panic!("{}: {}", input, error),
+    }
+}
+
+/// Like `pipeline` up to `define`, keeping the span of every term node.
+pub fn define_located(input: &str) -> Result<(Term, Vec<Span>), String> {
+    let spanned = lex::lex_spanned(input)?;
+    let lexed: Vec<Token> = spanned.iter().map(|(token, _)| *token).collect();
+    let parsed = parse::parse(&lexed)?;
+    let (mut constructed, spans) = construct::construct_located(&parsed, &parse::spans(&spanned))?;
+    define::define(&mut constructed);
+    Ok((constructed, spans))
+}
+
+/// Like `define_located` for every term, recovering from errors.
+pub fn recover(input: &str) -> Result<(Vec<Fragment>, Vec<SourceError>), String> {
+    let (trees, mut errors) = parse::parse_recovering(&lex::lex_spanned(input)?);
+    let mut fragments = Vec::new();
+    for (tree, spans) in trees {
+        let (terms, construct_errors) = construct::construct_recovering(&tree, &spans);
+        fragments.extend(terms);
+        errors.extend(construct_errors);
+    }
+    for (term, _) in &mut fragments {
+        define::define(term);
+    }
+    errors.sort_by_key(|(span, _)| span.start);
+    Ok((fragments, errors))
+}
+
+/// Rejects terms with free variables, reporting every free occurrence.
+pub fn check_closed(input: &str) -> Result<(), String> {
+    let (term, spans) = define_located(input)?;
+    let errors: Vec<String> = free::free_vars(&term)
+        .into_iter()
+        .map(|(index, name)| {
+            source::locate(input, spans[index], &format!("unbound variable `{}`", name))
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Lints a defined term, returning whether any lint is denied.
+pub fn lint(input: &str, options: &lint::Options) -> Result<(Vec<String>, bool), String> {
+    let (term, spans) = define_located(input)?;
+    let diagnostics = lint::lint(&term, options);
+    let denied = diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Deny);
+    let messages = diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let level = match diagnostic.severity {
+                Severity::Deny => "error",
+                _ => "warning",
+            };
+            let message = format!(
+                "{}[{}]: {}",
+                level,
+                diagnostic.lint.code(),
+                diagnostic.message
+            );
+            source::locate(input, spans[diagnostic.index], &message)
+        })
+        .collect();
+    Ok((messages, denied))
+}
+
+/// Reprints a program in canonical layout, wrapping at `width` columns.
+pub fn format(input: &str, width: usize) -> Result<String, String> {
+    let (trees, errors) = parse::parse_recovering(&lex::lex_spanned(input)?);
+    if let Some((span, err)) = errors.first() {
+        return Err(source::locate(input, *span, err));
+    }
+    Ok(trees
+        .iter()
+        .map(|(tree, _)| output::layout(tree, width))
+        .collect())
+}
+
+pub fn infer(input: &str) -> Result<Type, String> {
+    let (term, spans) = define_located(input)?;
+    infer::infer(&term).map_err(|(index, err)| source::locate(input, spans[index], &err))
+}
+
+/// Checks a System F term, then evaluates its erasure.
+pub fn system_f(input: &str) -> Result<(Term, Type), String> {
+    let parsed = parse::parse(&lex::lex(input)?)?;
+    let typed = systemf::construct(&parsed)?;
+    let ty = systemf::typecheck(&typed)?;
+    let mut erased = systemf::erase(&typed);
+    define::define(&mut erased);
+    eval::eval(&mut erased)?;
+    Ok((erased, ty))
+}
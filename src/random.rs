@@ -0,0 +1,247 @@
+//! Seeded random terms for property tests.
+
+use crate::construct;
+use crate::construct::Term;
+
+/// Xorshift64* pseudorandom number generator.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift gets stuck at zero, so mix the seed first.
+        let mut state = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        state ^= state >> 31;
+        Rng {
+            state: state.max(1),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `0..bound`. `bound` must not be zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    /// True with probability `percent` in 100.
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// The shape of generated terms.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Most nodes in a term. Closed terms have at least two.
+    pub size: usize,
+    /// How many names to use, starting from `x`.
+    pub names: usize,
+    /// Whether every variable must be bound.
+    pub closed: bool,
+    /// Percent chance of a lambda where an application could also go.
+    pub lambda: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            size: 12,
+            names: 3,
+            closed: true,
+            lambda: 50,
+        }
+    }
+}
+
+/// A random term of lambdas, applications and variables, not yet defined.
+pub fn term(rng: &mut Rng, options: &Options) -> Term {
+    let size = 1 + rng.below(options.size.max(1));
+    generate(rng, options, size, &mut Vec::new())
+}
+
+fn name(index: usize) -> char {
+    const NAMES: &str = "xyzabcdefghijklmnopqrstuvw";
+    NAMES.chars().nth(index % NAMES.len()).unwrap()
+}
+
+fn generate(rng: &mut Rng, options: &Options, size: usize, scope: &mut Vec<char>) -> Term {
+    let names = options.names.max(1);
+    if options.closed && scope.is_empty() {
+        return lambda(rng, options, size.max(2), scope);
+    }
+    if size == 1 {
+        return if options.closed {
+            construct::var(scope[rng.below(scope.len())])
+        } else {
+            construct::var(name(rng.below(names)))
+        };
+    }
+    if size == 2 || rng.chance(options.lambda) {
+        return lambda(rng, options, size, scope);
+    }
+    let left = 1 + rng.below(size - 2);
+    let func = generate(rng, options, left, scope);
+    let arg = generate(rng, options, size - 1 - left, scope);
+    construct::app(func, arg)
+}
+
+fn lambda(rng: &mut Rng, options: &Options, size: usize, scope: &mut Vec<char>) -> Term {
+    let name = name(rng.below(options.names.max(1)));
+    scope.push(name);
+    let body = generate(rng, options, size - 1, scope);
+    scope.pop();
+    construct::lambda(name, body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::arena;
+    use crate::debruijn;
+    use crate::define;
+    use crate::eval;
+    use crate::free;
+    use crate::need;
+    use crate::ski;
+
+    const CASES: u64 = 500;
+
+    /// Defined terms from each seed, alternating between closed and open.
+    fn terms() -> impl Iterator<Item = (u64, Term)> {
+        (0..CASES).map(|seed| {
+            let options = Options {
+                closed: seed % 2 == 0,
+                ..Options::default()
+            };
+            let mut term = term(&mut Rng::new(seed), &options);
+            define::define(&mut term);
+            (seed, term)
+        })
+    }
+
+    fn has_redex(term: &Term) -> bool {
+        match term {
+            Term::Var(..) => false,
+            Term::Lambda(_, _, _, body) | Term::Def(_, _, body) => has_redex(body),
+            Term::App(func, arg) => {
+                matches!(**func, Term::Lambda(..)) || has_redex(func) || has_redex(arg)
+            }
+            Term::Let(..) => true,
+        }
+    }
+
+    /// Checks that variables carry their binder's id and binder ids are distinct.
+    fn check_ids(term: &Term, scope: &mut Vec<(char, usize)>, binders: &mut Vec<usize>) {
+        match term {
+            Term::Var(name, id) => {
+                let id = id.unwrap();
+                match scope.iter().rev().find(|(binder, _)| binder == name) {
+                    Some((_, binder_id)) => assert_eq!(id, *binder_id),
+                    None => assert!(!binders.contains(&id)),
+                }
+            }
+            Term::Lambda(name, id, _, body) | Term::Def(name, id, body) => {
+                let id = id.unwrap();
+                assert!(!binders.contains(&id));
+                binders.push(id);
+                scope.push((*name, id));
+                check_ids(body, scope, binders);
+                scope.pop();
+            }
+            Term::App(func, arg) => {
+                check_ids(func, scope, binders);
+                check_ids(arg, scope, binders);
+            }
+            Term::Let(name, id, _, value, body) => {
+                check_ids(value, scope, binders);
+                let id = id.unwrap();
+                assert!(!binders.contains(&id));
+                binders.push(id);
+                scope.push((*name, id));
+                check_ids(body, scope, binders);
+                scope.pop();
+            }
+        }
+    }
+
+    #[test]
+    fn generator() {
+        let first: Vec<Term> = terms().map(|(_, term)| term).collect();
+        let second: Vec<Term> = terms().map(|(_, term)| term).collect();
+        assert_eq!(first, second);
+
+        let options = Options::default();
+        for (seed, term) in terms() {
+            assert!(construct::size(&term) <= options.size, "seed {}", seed);
+            if seed % 2 == 0 {
+                assert!(free::free_vars(&term).is_empty(), "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn ids() {
+        for (_, term) in terms() {
+            check_ids(&term, &mut Vec::new(), &mut Vec::new());
+        }
+    }
+
+    #[test]
+    fn strategies() {
+        let options = eval::Options {
+            limit: Some(100),
+            ..eval::Options::default()
+        };
+        for (seed, term) in terms() {
+            let mut tree = term.clone();
+            let steps = match eval::eval_with(&mut tree, &options) {
+                Ok(stats) => stats.beta_steps,
+                Err(_) => continue,
+            };
+            assert!(!has_redex(&tree), "seed {}: {}", seed, tree);
+
+            let mut shared = term.clone();
+            need::eval(&mut shared).unwrap();
+            assert!(
+                debruijn::alpha_equivalent(&tree, &shared),
+                "seed {}: {} and {}",
+                seed,
+                tree,
+                shared
+            );
+
+            let mut interned = term.clone();
+            assert_eq!(arena::eval(&mut interned), Ok(steps), "seed {}", seed);
+            assert!(
+                debruijn::alpha_equivalent(&tree, &interned),
+                "seed {}: {} and {}",
+                seed,
+                tree,
+                interned
+            );
+
+            // Combinators only take closed terms, and can still hit their own limits.
+            let combinators = ski::translate(&term, &ski::Options::default())
+                .and_then(|combinators| ski::reduce(&combinators));
+            if let Ok((readback, _)) = combinators {
+                assert!(
+                    debruijn::alpha_equivalent(&tree, &readback),
+                    "seed {}: {} and {}",
+                    seed,
+                    tree,
+                    readback
+                );
+            }
+        }
+    }
+}
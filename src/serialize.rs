@@ -0,0 +1,665 @@
+//! Stable JSON and S-expression forms of every pipeline stage.
+//!
+//! JSON:
+//!
+//! - tokens are an array of `{"token": kind}` with kind `open`, `close`,
+//!   `open_bracket`, `close_bracket`, `arrow`, `colon`, `assert`, `equals`,
+//!   `let`, `rec`, `in`, `where` or `bind`, and
+//!   `{"token": "name", "name": "x"}`
+//! - trees are `{"tree": "name", "name": "x"}`, `{"tree": kind}` with kind
+//!   `arrow`, `colon`, `assert`, `equals`, `let`, `rec`, `in`, `where` or
+//!   `bind`, and
+//!   `{"tree": "branch" | "bracket", "children": [..]}`
+//! - terms are `{"term": "var", "name", "id"}`,
+//!   `{"term": "lambda", "name", "id", "type", "body"}`,
+//!   `{"term": "app", "func", "arg"}`, `{"term": "def", "name", "id", "body"}`
+//!   and `{"term": "let", "name", "id", "rec", "value", "body"}`, where `id`
+//!   is `null` before `define`, `type` is `null` when unannotated and `rec`
+//!   is `true` for a `let rec`
+//! - types are `{"type": "var", "name"}`, `{"type": "arrow", "param", "result"}`
+//!   and `{"type": "forall", "name", "body"}`
+//!
+//! S-expressions use the same names, with `_` for a missing id or type and
+//! `let rec` for a recursive `let`. For example, `--format sexp` gives:
+//!
+//! ```text
+//! --stage lex '(x -> x)'       (tokens open (name x) arrow (name x) close)
+//! --stage parse '(x -> x)'     (branch (name x) arrow (name x))
+//! --stage define '(f a)'       (app (var f 0) (var a 1))
+//! --stage define '(x -> x)'    (lambda x 0 _ (var x 0))
+//! --stage define '(f : f)'     (def f 0 (var f 0))
+//! --stage define '(let i = (x -> x) in i)'
+//!                              (let i 0 (lambda x 1 _ (var x 1)) (var i 0))
+//! --stage define '(x [[a] -> a -> b] -> x)'
+//!                              (lambda x 0 (forall a (arrow (tvar a) (tvar b))) (var x 0))
+//! ```
+
+use crate::construct::Term;
+use crate::json;
+use crate::json::Json;
+use crate::lex::Token;
+use crate::parse::Tree;
+use crate::pipeline::PipelineResult;
+use crate::sexp;
+use crate::sexp::Sexp;
+use crate::types;
+use crate::types::Type;
+
+pub fn to_json(result: &PipelineResult) -> Json {
+    match result {
+        PipelineResult::Tokens(tokens) => Json::Array(tokens.iter().map(token_to_json).collect()),
+        PipelineResult::Tree(tree) => tree_to_json(tree),
+        PipelineResult::Term(term) => term_to_json(term),
+    }
+}
+
+pub fn from_json(json: &Json) -> Result<PipelineResult, String> {
+    if let Some(tokens) = json.as_array() {
+        return Ok(PipelineResult::Tokens(
+            tokens
+                .iter()
+                .map(token_from_json)
+                .collect::<Result<_, _>>()?,
+        ));
+    }
+    if json.get("tree").is_some() {
+        return Ok(PipelineResult::Tree(tree_from_json(json)?));
+    }
+    Ok(PipelineResult::Term(term_from_json(json)?))
+}
+
+pub fn to_sexp(result: &PipelineResult) -> Sexp {
+    match result {
+        PipelineResult::Tokens(tokens) => {
+            let mut items = vec![sexp::atom("tokens")];
+            items.extend(tokens.iter().map(token_to_sexp));
+            Sexp::List(items)
+        }
+        PipelineResult::Tree(tree) => tree_to_sexp(tree),
+        PipelineResult::Term(term) => term_to_sexp(term),
+    }
+}
+
+pub fn from_sexp(sexp: &Sexp) -> Result<PipelineResult, String> {
+    match sexp {
+        Sexp::List(items) => match items.first().and_then(Sexp::as_atom) {
+            Some("tokens") => Ok(PipelineResult::Tokens(
+                items[1..]
+                    .iter()
+                    .map(token_from_sexp)
+                    .collect::<Result<_, _>>()?,
+            )),
+            Some("var" | "lambda" | "app" | "def" | "let") => {
+                Ok(PipelineResult::Term(term_from_sexp(sexp)?))
+            }
+            _ => Ok(PipelineResult::Tree(tree_from_sexp(sexp)?)),
+        },
+        Sexp::Atom(_) => Ok(PipelineResult::Tree(tree_from_sexp(sexp)?)),
+    }
+}
+
+fn name_to_json(name: char) -> Json {
+    Json::String(name.to_string())
+}
+
+fn name_from_json(json: Option<&Json>) -> Result<char, String> {
+    name_from_str(json.and_then(Json::as_str))
+}
+
+fn name_from_str(name: Option<&str>) -> Result<char, String> {
+    let mut chars = name.unwrap_or_default().chars();
+    match (chars.next(), chars.next()) {
+        (Some(name), None) => Ok(name),
+        _ => Err(String::from("Expected a one-character name")),
+    }
+}
+
+fn id_to_json(id: Option<usize>) -> Json {
+    match id {
+        Some(id) => Json::Number(id as f64),
+        None => Json::Null,
+    }
+}
+
+fn id_from_json(json: Option<&Json>) -> Result<Option<usize>, String> {
+    match json {
+        None | Some(Json::Null) => Ok(None),
+        Some(json) => match json.as_usize() {
+            Some(id) => Ok(Some(id)),
+            None => Err(String::from("Expected an id")),
+        },
+    }
+}
+
+fn field<'a>(json: &'a Json, key: &str) -> Result<&'a Json, String> {
+    json.get(key)
+        .ok_or_else(|| format!("Missing field '{}'", key))
+}
+
+fn token_to_json(token: &Token) -> Json {
+    let kind = match token {
+        Token::Name(name) => {
+            return json::object(vec![
+                ("token", json::string("name")),
+                ("name", name_to_json(*name)),
+            ])
+        }
+        Token::Arrow => "arrow",
+        Token::Colon => "colon",
+        Token::Assert => "assert",
+        Token::Equals => "equals",
+        Token::Let => "let",
+        Token::Rec => "rec",
+        Token::In => "in",
+        Token::Where => "where",
+        Token::Bind => "bind",
+        Token::Open => "open",
+        Token::Close => "close",
+        Token::OpenBracket => "open_bracket",
+        Token::CloseBracket => "close_bracket",
+    };
+    json::object(vec![("token", json::string(kind))])
+}
+
+fn token_from_json(json: &Json) -> Result<Token, String> {
+    match field(json, "token")?.as_str() {
+        Some("name") => Ok(Token::Name(name_from_json(json.get("name"))?)),
+        Some(kind) => token_from_kind(kind),
+        None => Err(String::from("Expected a token kind")),
+    }
+}
+
+fn token_from_kind(kind: &str) -> Result<Token, String> {
+    match kind {
+        "arrow" => Ok(Token::Arrow),
+        "colon" => Ok(Token::Colon),
+        "assert" => Ok(Token::Assert),
+        "equals" => Ok(Token::Equals),
+        "let" => Ok(Token::Let),
+        "rec" => Ok(Token::Rec),
+        "in" => Ok(Token::In),
+        "where" => Ok(Token::Where),
+        "bind" => Ok(Token::Bind),
+        "open" => Ok(Token::Open),
+        "close" => Ok(Token::Close),
+        "open_bracket" => Ok(Token::OpenBracket),
+        "close_bracket" => Ok(Token::CloseBracket),
+        _ => Err(format!("Unknown token '{}'", kind)),
+    }
+}
+
+fn tree_to_json(tree: &Tree) -> Json {
+    match tree {
+        Tree::Arrow => json::object(vec![("tree", json::string("arrow"))]),
+        Tree::Colon => json::object(vec![("tree", json::string("colon"))]),
+        Tree::Assert => json::object(vec![("tree", json::string("assert"))]),
+        Tree::Equals => json::object(vec![("tree", json::string("equals"))]),
+        Tree::Let => json::object(vec![("tree", json::string("let"))]),
+        Tree::Rec => json::object(vec![("tree", json::string("rec"))]),
+        Tree::In => json::object(vec![("tree", json::string("in"))]),
+        Tree::Where => json::object(vec![("tree", json::string("where"))]),
+        Tree::Bind => json::object(vec![("tree", json::string("bind"))]),
+        Tree::Name(name) => json::object(vec![
+            ("tree", json::string("name")),
+            ("name", name_to_json(*name)),
+        ]),
+        Tree::Branch(children) | Tree::Bracket(children) => {
+            let kind = match tree {
+                Tree::Branch(_) => "branch",
+                _ => "bracket",
+            };
+            json::object(vec![
+                ("tree", json::string(kind)),
+                (
+                    "children",
+                    Json::Array(children.iter().map(tree_to_json).collect()),
+                ),
+            ])
+        }
+    }
+}
+
+fn tree_from_json(json: &Json) -> Result<Tree, String> {
+    let children = || -> Result<Vec<Tree>, String> {
+        match field(json, "children")?.as_array() {
+            Some(children) => children.iter().map(tree_from_json).collect(),
+            None => Err(String::from("Expected an array of children")),
+        }
+    };
+    match field(json, "tree")?.as_str() {
+        Some("arrow") => Ok(Tree::Arrow),
+        Some("colon") => Ok(Tree::Colon),
+        Some("assert") => Ok(Tree::Assert),
+        Some("equals") => Ok(Tree::Equals),
+        Some("let") => Ok(Tree::Let),
+        Some("rec") => Ok(Tree::Rec),
+        Some("in") => Ok(Tree::In),
+        Some("where") => Ok(Tree::Where),
+        Some("bind") => Ok(Tree::Bind),
+        Some("name") => Ok(Tree::Name(name_from_json(json.get("name"))?)),
+        Some("branch") => Ok(Tree::Branch(children()?)),
+        Some("bracket") => Ok(Tree::Bracket(children()?)),
+        _ => Err(String::from("Unknown tree")),
+    }
+}
+
+fn term_to_json(term: &Term) -> Json {
+    match term {
+        Term::Var(name, id) => json::object(vec![
+            ("term", json::string("var")),
+            ("name", name_to_json(*name)),
+            ("id", id_to_json(*id)),
+        ]),
+        Term::Lambda(name, id, ty, body) => json::object(vec![
+            ("term", json::string("lambda")),
+            ("name", name_to_json(*name)),
+            ("id", id_to_json(*id)),
+            ("type", ty.as_ref().map(type_to_json).unwrap_or(Json::Null)),
+            ("body", term_to_json(body)),
+        ]),
+        Term::App(func, arg) => json::object(vec![
+            ("term", json::string("app")),
+            ("func", term_to_json(func)),
+            ("arg", term_to_json(arg)),
+        ]),
+        Term::Def(name, id, body) => json::object(vec![
+            ("term", json::string("def")),
+            ("name", name_to_json(*name)),
+            ("id", id_to_json(*id)),
+            ("body", term_to_json(body)),
+        ]),
+        Term::Let(name, id, rec, value, body) => json::object(vec![
+            ("term", json::string("let")),
+            ("name", name_to_json(*name)),
+            ("id", id_to_json(*id)),
+            ("rec", Json::Bool(*rec)),
+            ("value", term_to_json(value)),
+            ("body", term_to_json(body)),
+        ]),
+    }
+}
+
+fn term_from_json(json: &Json) -> Result<Term, String> {
+    let name = || name_from_json(json.get("name"));
+    let id = || id_from_json(json.get("id"));
+    let term =
+        |key| -> Result<Box<Term>, String> { Ok(Box::new(term_from_json(field(json, key)?)?)) };
+    match field(json, "term")?.as_str() {
+        Some("var") => Ok(Term::Var(name()?, id()?)),
+        Some("lambda") => {
+            let ty = match json.get("type") {
+                None | Some(Json::Null) => None,
+                Some(ty) => Some(type_from_json(ty)?),
+            };
+            Ok(Term::Lambda(name()?, id()?, ty, term("body")?))
+        }
+        Some("app") => Ok(Term::App(term("func")?, term("arg")?)),
+        Some("def") => Ok(Term::Def(name()?, id()?, term("body")?)),
+        Some("let") => {
+            let rec = match json.get("rec") {
+                None | Some(Json::Bool(false)) => false,
+                Some(Json::Bool(true)) => true,
+                Some(_) => return Err(String::from("Expected `rec` to be a boolean")),
+            };
+            Ok(Term::Let(
+                name()?,
+                id()?,
+                rec,
+                term("value")?,
+                term("body")?,
+            ))
+        }
+        _ => Err(String::from("Unknown term")),
+    }
+}
+
+fn type_to_json(ty: &Type) -> Json {
+    match ty {
+        Type::Var(name) => json::object(vec![
+            ("type", json::string("var")),
+            ("name", name_to_json(*name)),
+        ]),
+        Type::Arrow(param, result) => json::object(vec![
+            ("type", json::string("arrow")),
+            ("param", type_to_json(param)),
+            ("result", type_to_json(result)),
+        ]),
+        Type::Forall(name, body) => json::object(vec![
+            ("type", json::string("forall")),
+            ("name", name_to_json(*name)),
+            ("body", type_to_json(body)),
+        ]),
+    }
+}
+
+fn type_from_json(json: &Json) -> Result<Type, String> {
+    let ty = |key| type_from_json(field(json, key)?);
+    match field(json, "type")?.as_str() {
+        Some("var") => Ok(types::var(name_from_json(json.get("name"))?)),
+        Some("arrow") => Ok(types::arrow(ty("param")?, ty("result")?)),
+        Some("forall") => Ok(types::forall(
+            name_from_json(json.get("name"))?,
+            ty("body")?,
+        )),
+        _ => Err(String::from("Unknown type")),
+    }
+}
+
+fn list(items: Vec<Sexp>) -> Sexp {
+    Sexp::List(items)
+}
+
+fn name_to_sexp(name: char) -> Sexp {
+    Sexp::Atom(name.to_string())
+}
+
+fn id_to_sexp(id: Option<usize>) -> Sexp {
+    match id {
+        Some(id) => Sexp::Atom(id.to_string()),
+        None => sexp::atom("_"),
+    }
+}
+
+fn id_from_sexp(sexp: &Sexp) -> Result<Option<usize>, String> {
+    match sexp.as_atom() {
+        Some("_") => Ok(None),
+        Some(atom) => atom
+            .parse()
+            .map(Some)
+            .map_err(|_| String::from("Expected an id")),
+        None => Err(String::from("Expected an id")),
+    }
+}
+
+fn token_to_sexp(token: &Token) -> Sexp {
+    match token {
+        Token::Name(name) => list(vec![sexp::atom("name"), name_to_sexp(*name)]),
+        Token::Arrow => sexp::atom("arrow"),
+        Token::Colon => sexp::atom("colon"),
+        Token::Assert => sexp::atom("assert"),
+        Token::Equals => sexp::atom("equals"),
+        Token::Let => sexp::atom("let"),
+        Token::Rec => sexp::atom("rec"),
+        Token::In => sexp::atom("in"),
+        Token::Where => sexp::atom("where"),
+        Token::Bind => sexp::atom("bind"),
+        Token::Open => sexp::atom("open"),
+        Token::Close => sexp::atom("close"),
+        Token::OpenBracket => sexp::atom("open_bracket"),
+        Token::CloseBracket => sexp::atom("close_bracket"),
+    }
+}
+
+fn token_from_sexp(sexp: &Sexp) -> Result<Token, String> {
+    match sexp {
+        Sexp::Atom(kind) => token_from_kind(kind),
+        Sexp::List(items) => match items.as_slice() {
+            [kind, name] if kind.as_atom() == Some("name") => {
+                Ok(Token::Name(name_from_str(name.as_atom())?))
+            }
+            _ => Err(String::from("Unknown token")),
+        },
+    }
+}
+
+fn tree_to_sexp(tree: &Tree) -> Sexp {
+    match tree {
+        Tree::Arrow => sexp::atom("arrow"),
+        Tree::Colon => sexp::atom("colon"),
+        Tree::Assert => sexp::atom("assert"),
+        Tree::Equals => sexp::atom("equals"),
+        Tree::Let => sexp::atom("let"),
+        Tree::Rec => sexp::atom("rec"),
+        Tree::In => sexp::atom("in"),
+        Tree::Where => sexp::atom("where"),
+        Tree::Bind => sexp::atom("bind"),
+        Tree::Name(name) => list(vec![sexp::atom("name"), name_to_sexp(*name)]),
+        Tree::Branch(children) | Tree::Bracket(children) => {
+            let kind = match tree {
+                Tree::Branch(_) => "branch",
+                _ => "bracket",
+            };
+            let mut items = vec![sexp::atom(kind)];
+            items.extend(children.iter().map(tree_to_sexp));
+            list(items)
+        }
+    }
+}
+
+fn tree_from_sexp(sexp: &Sexp) -> Result<Tree, String> {
+    match sexp {
+        Sexp::Atom(atom) => match atom.as_str() {
+            "arrow" => Ok(Tree::Arrow),
+            "colon" => Ok(Tree::Colon),
+            "assert" => Ok(Tree::Assert),
+            "equals" => Ok(Tree::Equals),
+            "let" => Ok(Tree::Let),
+            "rec" => Ok(Tree::Rec),
+            "in" => Ok(Tree::In),
+            "where" => Ok(Tree::Where),
+            "bind" => Ok(Tree::Bind),
+            _ => Err(format!("Unknown tree '{}'", atom)),
+        },
+        Sexp::List(items) => {
+            let children =
+                || -> Result<Vec<Tree>, String> { items[1..].iter().map(tree_from_sexp).collect() };
+            match (items.first().and_then(Sexp::as_atom), items.len()) {
+                (Some("name"), 2) => Ok(Tree::Name(name_from_str(items[1].as_atom())?)),
+                (Some("branch"), _) => Ok(Tree::Branch(children()?)),
+                (Some("bracket"), _) => Ok(Tree::Bracket(children()?)),
+                _ => Err(String::from("Unknown tree")),
+            }
+        }
+    }
+}
+
+fn term_to_sexp(term: &Term) -> Sexp {
+    match term {
+        Term::Var(name, id) => list(vec![
+            sexp::atom("var"),
+            name_to_sexp(*name),
+            id_to_sexp(*id),
+        ]),
+        Term::Lambda(name, id, ty, body) => list(vec![
+            sexp::atom("lambda"),
+            name_to_sexp(*name),
+            id_to_sexp(*id),
+            ty.as_ref().map(type_to_sexp).unwrap_or(sexp::atom("_")),
+            term_to_sexp(body),
+        ]),
+        Term::App(func, arg) => list(vec![
+            sexp::atom("app"),
+            term_to_sexp(func),
+            term_to_sexp(arg),
+        ]),
+        Term::Def(name, id, body) => list(vec![
+            sexp::atom("def"),
+            name_to_sexp(*name),
+            id_to_sexp(*id),
+            term_to_sexp(body),
+        ]),
+        Term::Let(name, id, rec, value, body) => {
+            let mut items = vec![sexp::atom("let")];
+            if *rec {
+                items.push(sexp::atom("rec"));
+            }
+            items.extend([
+                name_to_sexp(*name),
+                id_to_sexp(*id),
+                term_to_sexp(value),
+                term_to_sexp(body),
+            ]);
+            list(items)
+        }
+    }
+}
+
+fn term_from_sexp(sexp: &Sexp) -> Result<Term, String> {
+    let items = match sexp {
+        Sexp::List(items) => items.as_slice(),
+        Sexp::Atom(_) => return Err(String::from("Expected a term")),
+    };
+    let term = |sexp| -> Result<Box<Term>, String> { Ok(Box::new(term_from_sexp(sexp)?)) };
+    match items {
+        [kind, name, id] if kind.as_atom() == Some("var") => {
+            Ok(Term::Var(name_from_str(name.as_atom())?, id_from_sexp(id)?))
+        }
+        [kind, name, id, ty, body] if kind.as_atom() == Some("lambda") => {
+            let ty = match ty.as_atom() {
+                Some("_") => None,
+                _ => Some(type_from_sexp(ty)?),
+            };
+            Ok(Term::Lambda(
+                name_from_str(name.as_atom())?,
+                id_from_sexp(id)?,
+                ty,
+                term(body)?,
+            ))
+        }
+        [kind, func, arg] if kind.as_atom() == Some("app") => {
+            Ok(Term::App(term(func)?, term(arg)?))
+        }
+        [kind, name, id, body] if kind.as_atom() == Some("def") => Ok(Term::Def(
+            name_from_str(name.as_atom())?,
+            id_from_sexp(id)?,
+            term(body)?,
+        )),
+        [kind, name, id, value, body] if kind.as_atom() == Some("let") => Ok(Term::Let(
+            name_from_str(name.as_atom())?,
+            id_from_sexp(id)?,
+            false,
+            term(value)?,
+            term(body)?,
+        )),
+        [kind, rec, name, id, value, body]
+            if kind.as_atom() == Some("let") && rec.as_atom() == Some("rec") =>
+        {
+            Ok(Term::Let(
+                name_from_str(name.as_atom())?,
+                id_from_sexp(id)?,
+                true,
+                term(value)?,
+                term(body)?,
+            ))
+        }
+        _ => Err(String::from("Unknown term")),
+    }
+}
+
+fn type_to_sexp(ty: &Type) -> Sexp {
+    match ty {
+        Type::Var(name) => list(vec![sexp::atom("tvar"), name_to_sexp(*name)]),
+        Type::Arrow(param, result) => list(vec![
+            sexp::atom("arrow"),
+            type_to_sexp(param),
+            type_to_sexp(result),
+        ]),
+        Type::Forall(name, body) => list(vec![
+            sexp::atom("forall"),
+            name_to_sexp(*name),
+            type_to_sexp(body),
+        ]),
+    }
+}
+
+fn type_from_sexp(sexp: &Sexp) -> Result<Type, String> {
+    let items = match sexp {
+        Sexp::List(items) => items.as_slice(),
+        Sexp::Atom(_) => return Err(String::from("Expected a type")),
+    };
+    match items {
+        [kind, name] if kind.as_atom() == Some("tvar") => {
+            Ok(types::var(name_from_str(name.as_atom())?))
+        }
+        [kind, param, result] if kind.as_atom() == Some("arrow") => Ok(types::arrow(
+            type_from_sexp(param)?,
+            type_from_sexp(result)?,
+        )),
+        [kind, name, body] if kind.as_atom() == Some("forall") => Ok(types::forall(
+            name_from_str(name.as_atom())?,
+            type_from_sexp(body)?,
+        )),
+        _ => Err(String::from("Unknown type")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline;
+    use crate::pipeline::Pipeline;
+
+    macro_rules! round_trip {
+        ($stage:expr, $text:literal) => {
+            let result = pipeline::pipeline($stage, $text).unwrap();
+            let json = json::parse(&format!("{}", to_json(&result))).unwrap();
+            assert_eq!(from_json(&json), Ok(result));
+            let result = pipeline::pipeline($stage, $text).unwrap();
+            let sexp = sexp::parse(&format!("{}", to_sexp(&result))).unwrap();
+            assert_eq!(from_sexp(&sexp), Ok(result));
+        };
+    }
+
+    macro_rules! serialized {
+        ($stage:expr, $text:literal, $json:literal, $sexp:literal) => {
+            let result = pipeline::pipeline($stage, $text).unwrap();
+            assert_eq!(format!("{}", to_json(&result)), $json);
+            assert_eq!(format!("{}", to_sexp(&result)), $sexp);
+        };
+    }
+
+    #[test]
+    fn test() {
+        serialized!(
+            Pipeline::Lex,
+            "(x -> x)",
+            r#"[{"token":"open"},{"token":"name","name":"x"},{"token":"arrow"},{"token":"name","name":"x"},{"token":"close"}]"#,
+            "(tokens open (name x) arrow (name x) close)"
+        );
+        serialized!(
+            Pipeline::Parse,
+            "(f x)",
+            r#"{"tree":"branch","children":[{"tree":"name","name":"f"},{"tree":"name","name":"x"}]}"#,
+            "(branch (name f) (name x))"
+        );
+        serialized!(
+            Pipeline::Define,
+            "(x [a] -> y)",
+            r#"{"term":"lambda","name":"x","id":0,"type":{"type":"var","name":"a"},"body":{"term":"var","name":"y","id":1}}"#,
+            "(lambda x 0 (tvar a) (var y 1))"
+        );
+        serialized!(
+            Pipeline::Construct,
+            "(f : (f x))",
+            r#"{"term":"def","name":"f","id":null,"body":{"term":"app","func":{"term":"var","name":"f","id":null},"arg":{"term":"var","name":"x","id":null}}}"#,
+            "(def f _ (app (var f _) (var x _)))"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for stage in &["lex", "parse", "construct", "define", "eval"] {
+            let stage = || match *stage {
+                "lex" => Pipeline::Lex,
+                "parse" => Pipeline::Parse,
+                "construct" => Pipeline::Construct,
+                "define" => Pipeline::Define,
+                _ => Pipeline::Eval,
+            };
+            round_trip!(stage(), "x");
+            round_trip!(stage(), "((x -> (x -> x)) (y -> y))");
+            round_trip!(stage(), "(f : (x [[a] -> a -> b] -> x))");
+            round_trip!(stage(), "(g : (f x))");
+            round_trip!(stage(), "(let i = (x -> x) in (i i))");
+            round_trip!(stage(), "(let rec f = (x -> x) in f)");
+        }
+        assert_eq!(
+            from_json(&json::parse(r#"{"term":"var","name":"xy"}"#).unwrap()),
+            Err(String::from("Expected a one-character name"))
+        );
+        assert_eq!(
+            from_sexp(&sexp::parse("(lambda x 0 _)").unwrap()),
+            Err(String::from("Unknown term"))
+        );
+    }
+}
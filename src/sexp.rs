@@ -0,0 +1,99 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl Sexp {
+    pub fn as_atom(&self) -> Option<&str> {
+        match self {
+            Sexp::Atom(atom) => Some(atom),
+            Sexp::List(_) => None,
+        }
+    }
+}
+
+pub fn atom(value: &str) -> Sexp {
+    Sexp::Atom(String::from(value))
+}
+
+pub fn parse(input: &str) -> Result<Sexp, String> {
+    let mut chars = input.chars().peekable();
+    let sexp = do_parse(&mut chars)?;
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        Some(ch) => Err(format!("Unexpected '{}' after expression", ch)),
+        None => Ok(sexp),
+    }
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while let Some(' ' | '\n' | '\r' | '\t') = chars.peek() {
+        chars.next();
+    }
+}
+
+fn do_parse(chars: &mut Chars) -> Result<Sexp, String> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some('(') => {
+            let mut items = Vec::new();
+            loop {
+                skip_whitespace(chars);
+                match chars.peek() {
+                    Some(')') => {
+                        chars.next();
+                        return Ok(Sexp::List(items));
+                    }
+                    Some(_) => items.push(do_parse(chars)?),
+                    None => return Err(String::from("Expected ')' before end of input")),
+                }
+            }
+        }
+        Some(')') => Err(String::from("Unexpected ')'")),
+        Some(ch) => {
+            let mut atom = String::from(ch);
+            while let Some(&ch) = chars.peek() {
+                if ch == '(' || ch == ')' || ch.is_whitespace() {
+                    break;
+                }
+                atom.push(ch);
+                chars.next();
+            }
+            Ok(Sexp::Atom(atom))
+        }
+        None => Err(String::from("Unexpected end of input")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let sexp = parse(" (lambda x 0\n (var x 0)) ").unwrap();
+        assert_eq!(
+            sexp,
+            Sexp::List(vec![
+                atom("lambda"),
+                atom("x"),
+                atom("0"),
+                Sexp::List(vec![atom("var"), atom("x"), atom("0")]),
+            ])
+        );
+        assert_eq!(format!("{}", sexp), "(lambda x 0 (var x 0))");
+        assert_eq!(parse("()"), Ok(Sexp::List(Vec::new())));
+        assert_eq!(
+            parse("(a"),
+            Err(String::from("Expected ')' before end of input"))
+        );
+        assert_eq!(
+            parse("a b"),
+            Err(String::from("Unexpected 'b' after expression"))
+        );
+        assert_eq!(parse(")"), Err(String::from("Unexpected ')'")));
+    }
+}
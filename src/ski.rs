@@ -0,0 +1,338 @@
+use crate::construct::Term;
+use crate::define;
+use crate::free;
+use std::collections::HashSet;
+
+const RECURSION_LIMIT: usize = 1000;
+const STEP_LIMIT: usize = 10_000;
+
+/// Combinator expression. Variables are constants to the reducer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ski {
+    S,
+    K,
+    I,
+    B,
+    C,
+    Var(char, usize),
+    App(Box<Ski>, Box<Ski>),
+}
+
+#[derive(Debug, Default)]
+pub struct Options {
+    /// Use the B and C combinators and eta-reduce, so results are eta-normal.
+    pub bc: bool,
+}
+
+pub fn app(func: Ski, arg: Ski) -> Ski {
+    Ski::App(Box::new(func), Box::new(arg))
+}
+
+/// Translates a closed, defined term into combinators by bracket abstraction.
+pub fn translate(term: &Term, options: &Options) -> Result<Ski, String> {
+    if let Some(name) = free::free_names(term).first() {
+        return Err(format!(
+            "Free variable `{}` cannot be translated to combinators",
+            name
+        ));
+    }
+    bracket(term, options)
+}
+
+fn bracket(term: &Term, options: &Options) -> Result<Ski, String> {
+    match term {
+        Term::Var(name, id) => Ok(Ski::Var(*name, define::id(*id)?)),
+        Term::Lambda(_, id, _, body) => Ok(abstract_var(
+            define::id(*id)?,
+            bracket(body, options)?,
+            options,
+        )),
+        Term::App(func, arg) => Ok(app(bracket(func, options)?, bracket(arg, options)?)),
+        Term::Let(_, id, _, value, body) => Ok(app(
+            abstract_var(define::id(*id)?, bracket(body, options)?, options),
+            bracket(value, options)?,
+        )),
+        Term::Def(..) => Err(String::from(
+            "Definitions cannot be translated to combinators",
+        )),
+    }
+}
+
+fn abstract_var(id: usize, body: Ski, options: &Options) -> Ski {
+    if !occurs(id, &body) {
+        return app(Ski::K, body);
+    }
+    match body {
+        Ski::App(func, arg) if options.bc => match (occurs(id, &func), &*arg) {
+            (false, Ski::Var(_, var)) if *var == id => *func,
+            (false, _) => app(app(Ski::B, *func), abstract_var(id, *arg, options)),
+            (true, _) if !occurs(id, &arg) => {
+                app(app(Ski::C, abstract_var(id, *func, options)), *arg)
+            }
+            (true, _) => s(id, *func, *arg, options),
+        },
+        Ski::App(func, arg) => s(id, *func, *arg, options),
+        _ => Ski::I,
+    }
+}
+
+fn s(id: usize, func: Ski, arg: Ski, options: &Options) -> Ski {
+    app(
+        app(Ski::S, abstract_var(id, func, options)),
+        abstract_var(id, arg, options),
+    )
+}
+
+fn occurs(id: usize, ski: &Ski) -> bool {
+    match ski {
+        Ski::Var(_, var) => *var == id,
+        Ski::App(func, arg) => occurs(id, func) || occurs(id, arg),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Cell {
+    Atom(Ski),
+    App(usize, usize),
+    Ind(usize),
+}
+
+/// Combinator graph, updated in place so shared redexes reduce once.
+struct Graph {
+    cells: Vec<Cell>,
+    steps: usize,
+    recursion: usize,
+    next_id: usize,
+    names: Vec<char>,
+}
+
+/// Reduces `ski` and reads it back, returning the combinator steps.
+pub fn reduce(ski: &Ski) -> Result<(Term, usize), String> {
+    let mut used = HashSet::new();
+    let mut next_id = 0;
+    variables(ski, &mut used, &mut next_id);
+    let mut graph = Graph {
+        cells: Vec::new(),
+        steps: 0,
+        recursion: 0,
+        next_id,
+        names: ('a'..='z').filter(|name| !used.contains(name)).collect(),
+    };
+    let root = graph.build(ski);
+    let term = graph.readback(root, 0)?;
+    Ok((term, graph.steps))
+}
+
+fn variables(ski: &Ski, names: &mut HashSet<char>, next_id: &mut usize) {
+    match ski {
+        Ski::Var(name, id) => {
+            names.insert(*name);
+            *next_id = (*next_id).max(id + 1);
+        }
+        Ski::App(func, arg) => {
+            variables(func, names, next_id);
+            variables(arg, names, next_id);
+        }
+        _ => (),
+    }
+}
+
+fn arity(ski: &Ski) -> usize {
+    match ski {
+        Ski::I => 1,
+        Ski::K => 2,
+        Ski::S | Ski::B | Ski::C => 3,
+        _ => 0,
+    }
+}
+
+impl Graph {
+    fn push(&mut self, cell: Cell) -> usize {
+        self.cells.push(cell);
+        self.cells.len() - 1
+    }
+
+    fn build(&mut self, ski: &Ski) -> usize {
+        match ski {
+            Ski::App(func, arg) => {
+                let func = self.build(func);
+                let arg = self.build(arg);
+                self.push(Cell::App(func, arg))
+            }
+            _ => self.push(Cell::Atom(ski.clone())),
+        }
+    }
+
+    fn follow(&self, mut node: usize) -> usize {
+        while let Cell::Ind(next) = self.cells[node] {
+            node = next;
+        }
+        node
+    }
+
+    /// Returns the head atom and the spine, innermost application first.
+    fn unwind(&self, root: usize) -> (Ski, Vec<usize>) {
+        let mut spine = Vec::new();
+        let mut node = self.follow(root);
+        loop {
+            match &self.cells[node] {
+                Cell::App(func, _) => {
+                    spine.push(node);
+                    node = self.follow(*func);
+                }
+                Cell::Atom(atom) => {
+                    spine.reverse();
+                    return (atom.clone(), spine);
+                }
+                Cell::Ind(_) => unreachable!(),
+            }
+        }
+    }
+
+    fn arg(&self, node: usize) -> usize {
+        match self.cells[node] {
+            Cell::App(_, arg) => arg,
+            _ => unreachable!(),
+        }
+    }
+
+    fn whnf(&mut self, root: usize) -> Result<(), String> {
+        loop {
+            let (head, spine) = self.unwind(root);
+            let arity = arity(&head);
+            if arity == 0 || spine.len() < arity {
+                return Ok(());
+            }
+            self.steps += 1;
+            if self.steps > STEP_LIMIT {
+                return Err(String::from("Hit step limit"));
+            }
+            let args: Vec<usize> = spine[..arity].iter().map(|node| self.arg(*node)).collect();
+            let cell = match head {
+                Ski::I | Ski::K => Cell::Ind(args[0]),
+                Ski::S => {
+                    let left = self.push(Cell::App(args[0], args[2]));
+                    let right = self.push(Cell::App(args[1], args[2]));
+                    Cell::App(left, right)
+                }
+                Ski::B => Cell::App(args[0], self.push(Cell::App(args[1], args[2]))),
+                _ => Cell::App(self.push(Cell::App(args[0], args[2])), args[1]),
+            };
+            self.cells[spine[arity - 1]] = cell;
+        }
+    }
+
+    fn readback(&mut self, root: usize, depth: usize) -> Result<Term, String> {
+        self.recursion += 1;
+        if self.recursion >= RECURSION_LIMIT {
+            return Err(String::from("Hit recursion limit"));
+        }
+        self.whnf(root)?;
+        let (head, spine) = self.unwind(root);
+        let term = match head {
+            Ski::Var(name, id) => {
+                let mut term = Term::Var(name, Some(id));
+                for node in spine {
+                    let arg = self.readback(self.arg(node), depth)?;
+                    term = Term::App(Box::new(term), Box::new(arg));
+                }
+                term
+            }
+            _ => {
+                let name = match self.names.get(depth) {
+                    Some(name) => *name,
+                    None => return Err(String::from("Ran out of names for binders")),
+                };
+                let id = self.next_id;
+                self.next_id += 1;
+                let var = self.push(Cell::Atom(Ski::Var(name, id)));
+                let applied = self.push(Cell::App(root, var));
+                let body = self.readback(applied, depth + 1)?;
+                Term::Lambda(name, Some(id), None, Box::new(body))
+            }
+        };
+        self.recursion -= 1;
+        Ok(term)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eval;
+    use crate::pipeline::define;
+
+    macro_rules! translate {
+        ($text:literal, $bc:expr, $expected:literal) => {
+            let ski = translate(&define($text), &Options { bc: $bc }).unwrap();
+            assert_eq!(format!("{}", ski), $expected);
+        };
+    }
+
+    fn agrees(term: &Term, options: &Options) -> Result<bool, String> {
+        let (reduced, _) = reduce(&translate(term, options)?)?;
+        eval::equivalent(term, &reduced)
+    }
+
+    macro_rules! agrees {
+        ($text:literal) => {
+            let term = define($text);
+            assert_eq!(agrees(&term, &Options { bc: false }), Ok(true));
+            assert_eq!(agrees(&term, &Options { bc: true }), Ok(true));
+        };
+    }
+
+    #[test]
+    fn test() {
+        translate!("(x -> x)", false, "I");
+        translate!("(x -> (y -> x))", false, "((S (K K)) I)");
+        translate!("(x -> (y -> x))", true, "K");
+        translate!(
+            "(f -> (x -> (f x)))",
+            false,
+            "((S ((S (K S)) ((S (K K)) I))) (K I))"
+        );
+        translate!("(f -> (x -> (f x)))", true, "I");
+        translate!("(x -> (y -> (y x)))", true, "(C I)");
+    }
+
+    #[test]
+    fn reduction() {
+        agrees!("(x -> x)");
+        agrees!("(y -> ((x -> x) y))");
+        agrees!("(z -> ((x -> (y -> x)) z))");
+        agrees!("(f -> (x -> (f (f x))))");
+        agrees!("(g -> (((n -> (f -> (x -> (f ((n f) x))))) (f -> (x -> (f x)))) g))");
+        agrees!("(((m -> (n -> (f -> (x -> ((m f) ((n f) x)))))) (f -> (x -> (f x)))) (f -> (x -> (f (f x)))))");
+        agrees!("((y -> (a -> a)) ((x -> (x x)) (x -> (x x))))");
+        agrees!("(x -> (y -> (y x)))");
+
+        let (term, steps) =
+            reduce(&translate(&define("((x -> x) (y -> y))"), &Options::default()).unwrap())
+                .unwrap();
+        assert_eq!(format!("{}", term), "(a -> a)");
+        assert_eq!(steps, 2);
+
+        let omega = translate(&define("((x -> (x x)) (x -> (x x)))"), &Options::default()).unwrap();
+        assert_eq!(reduce(&omega), Err(String::from("Hit step limit")));
+        assert_eq!(
+            translate(&define("(f : f)"), &Options::default()),
+            Err(String::from(
+                "Definitions cannot be translated to combinators"
+            ))
+        );
+        assert_eq!(
+            translate(&define("((x -> x) y)"), &Options::default()),
+            Err(String::from(
+                "Free variable `y` cannot be translated to combinators"
+            ))
+        );
+
+        let free = ('a'..='z').fold(Ski::I, |ski, name| app(ski, Ski::Var(name, 0)));
+        assert_eq!(
+            reduce(&app(Ski::K, free)),
+            Err(String::from("Ran out of names for binders"))
+        );
+    }
+}
@@ -0,0 +1,29 @@
+/// Range of characters in the input, from `start` up to but excluding `end`.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// One-based line and column of a character offset.
+pub fn position(input: &str, offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut column = 1;
+    for ch in input.chars().take(offset) {
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+/// An error and the span of the input it was found at.
+pub type SourceError = (Span, String);
+
+pub fn locate(input: &str, span: Span, message: &str) -> String {
+    let (line, column) = position(input, span.start);
+    format!("{}:{}: {}", line, column, message)
+}
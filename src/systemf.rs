@@ -0,0 +1,557 @@
+use crate::construct;
+use crate::construct::Term;
+use crate::parse::Tree;
+use crate::types;
+use crate::types::Type;
+use std::collections::HashSet;
+
+/// Term of the polymorphic lambda calculus.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FTerm {
+    Var(char),
+    Lambda(char, Type, Box<FTerm>),
+    App(Box<FTerm>, Box<FTerm>),
+    TypeLambda(char, Box<FTerm>),
+    TypeApp(Box<FTerm>, Type),
+}
+
+/// Church encodings with their System F types, as `(name, source, type)`.
+pub const PRELUDE: &[(&str, &str, &str)] = &[
+    (
+        "zero",
+        "([a] -> (f [a -> a] -> (x [a] -> x)))",
+        "[a] -> (a -> a) -> a -> a",
+    ),
+    (
+        "one",
+        "([a] -> (f [a -> a] -> (x [a] -> (f x))))",
+        "[a] -> (a -> a) -> a -> a",
+    ),
+    (
+        "two",
+        "([a] -> (f [a -> a] -> (x [a] -> (f (f x)))))",
+        "[a] -> (a -> a) -> a -> a",
+    ),
+    (
+        "succ",
+        "(n [[a] -> (a -> a) -> a -> a] -> ([a] -> (f [a -> a] -> (x [a] -> (f (((n [a]) f) x))))))",
+        "([a] -> (a -> a) -> a -> a) -> [a] -> (a -> a) -> a -> a",
+    ),
+    (
+        "plus",
+        "(m [[a] -> (a -> a) -> a -> a] -> (n [[a] -> (a -> a) -> a -> a] -> ([a] -> (f [a -> a] -> (x [a] -> (((m [a]) f) (((n [a]) f) x)))))))",
+        "([a] -> (a -> a) -> a -> a) -> ([a] -> (a -> a) -> a -> a) -> [a] -> (a -> a) -> a -> a",
+    ),
+    (
+        "true",
+        "([a] -> (x [a] -> (y [a] -> x)))",
+        "[a] -> a -> a -> a",
+    ),
+    (
+        "false",
+        "([a] -> (x [a] -> (y [a] -> y)))",
+        "[a] -> a -> a -> a",
+    ),
+    (
+        "and",
+        "(p [[a] -> a -> a -> a] -> (q [[a] -> a -> a -> a] -> (((p [[a] -> a -> a -> a]) q) p)))",
+        "([a] -> a -> a -> a) -> ([a] -> a -> a -> a) -> [a] -> a -> a -> a",
+    ),
+    (
+        "or",
+        "(p [[a] -> a -> a -> a] -> (q [[a] -> a -> a -> a] -> (((p [[a] -> a -> a -> a]) p) q)))",
+        "([a] -> a -> a -> a) -> ([a] -> a -> a -> a) -> [a] -> a -> a -> a",
+    ),
+    (
+        "not",
+        "(p [[a] -> a -> a -> a] -> (((p [[a] -> a -> a -> a]) ([a] -> (x [a] -> (y [a] -> y)))) ([a] -> (x [a] -> (y [a] -> x)))))",
+        "([a] -> a -> a -> a) -> [a] -> a -> a -> a",
+    ),
+    (
+        "if",
+        "([c] -> (p [[a] -> a -> a -> a] -> (x [c] -> (y [c] -> (((p [c]) x) y)))))",
+        "[c] -> ([a] -> a -> a -> a) -> c -> c -> c",
+    ),
+    (
+        "iszero",
+        "(n [[a] -> (a -> a) -> a -> a] -> (((n [[a] -> a -> a -> a]) (z [[a] -> a -> a -> a] -> ([a] -> (x [a] -> (y [a] -> y))))) ([a] -> (x [a] -> (y [a] -> x)))))",
+        "([a] -> (a -> a) -> a -> a) -> [a] -> a -> a -> a",
+    ),
+    (
+        "pair",
+        "([a] -> ([b] -> (x [a] -> (y [b] -> ([c] -> (p [a -> b -> c] -> ((p x) y)))))))",
+        "[a] -> [b] -> a -> b -> [c] -> (a -> b -> c) -> c",
+    ),
+    (
+        "fst",
+        "([a] -> ([b] -> (p [[c] -> (a -> b -> c) -> c] -> ((p [a]) (x [a] -> (y [b] -> x))))))",
+        "[a] -> [b] -> ([c] -> (a -> b -> c) -> c) -> a",
+    ),
+    (
+        "snd",
+        "([a] -> ([b] -> (p [[c] -> (a -> b -> c) -> c] -> ((p [b]) (x [a] -> (y [b] -> y))))))",
+        "[a] -> [b] -> ([c] -> (a -> b -> c) -> c) -> b",
+    ),
+];
+
+/// Looks up the source of a prelude definition by name.
+pub fn prelude(name: &str) -> Option<&'static str> {
+    PRELUDE
+        .iter()
+        .find(|(entry, _, _)| *entry == name)
+        .map(|(_, source, _)| *source)
+}
+
+pub fn construct(tree: &Tree) -> Result<FTerm, String> {
+    match tree {
+        Tree::Name(name) => Ok(FTerm::Var(*name)),
+        Tree::Branch(branch) => construct_branch(branch),
+        Tree::Arrow => Err(String::from("Unexpected arrow")),
+        Tree::Colon => Err(String::from("Definitions are not supported in System F")),
+        Tree::Assert => Err(String::from("Assertions are only allowed at the top level")),
+        Tree::Equals => Err(String::from("Unexpected '=='")),
+        Tree::Let | Tree::Rec | Tree::In | Tree::Where | Tree::Bind => Err(String::from(
+            "Let expressions are not supported in System F",
+        )),
+        Tree::Bracket(_) => Err(String::from("Unexpected type")),
+    }
+}
+
+fn construct_branch(branch: &[Tree]) -> Result<FTerm, String> {
+    match branch {
+        [Tree::Name(name), Tree::Bracket(ty), Tree::Arrow, body] => Ok(FTerm::Lambda(
+            *name,
+            construct::construct_type(ty)?,
+            Box::new(construct(body)?),
+        )),
+        [Tree::Name(name), Tree::Arrow, ..] => {
+            Err(format!("Expected a type annotation on `{}`", name))
+        }
+        [_, Tree::Colon, ..] => Err(String::from("Definitions are not supported in System F")),
+        [Tree::Bracket(vars), Tree::Arrow, body] => match vars.as_slice() {
+            [Tree::Name(name)] => Ok(FTerm::TypeLambda(*name, Box::new(construct(body)?))),
+            _ => Err(String::from("Expected one type variable in brackets")),
+        },
+        [func, Tree::Bracket(ty)] => Ok(FTerm::TypeApp(
+            Box::new(construct(func)?),
+            construct::construct_type(ty)?,
+        )),
+        [func, arg] => Ok(FTerm::App(
+            Box::new(construct(func)?),
+            Box::new(construct(arg)?),
+        )),
+        _ => Err(String::from(
+            "Expected a lambda, type abstraction or application",
+        )),
+    }
+}
+
+/// Checks a closed System F term, returning its type.
+pub fn typecheck(term: &FTerm) -> Result<Type, String> {
+    let mut checker = Checker {
+        context: Vec::new(),
+        type_vars: Vec::new(),
+    };
+    checker.infer(term)
+}
+
+struct Checker {
+    context: Vec<(char, Type)>,
+    type_vars: Vec<char>,
+}
+
+impl Checker {
+    fn infer(&mut self, term: &FTerm) -> Result<Type, String> {
+        match term {
+            FTerm::Var(name) => match self.context.iter().rev().find(|(var, _)| var == name) {
+                Some((_, ty)) => Ok(ty.clone()),
+                None => Err(format!("Unbound variable `{}`", name)),
+            },
+            FTerm::Lambda(name, ty, body) => {
+                self.well_formed(ty)?;
+                self.context.push((*name, ty.clone()));
+                let result = self.infer(body);
+                self.context.pop();
+                Ok(types::arrow(ty.clone(), result?))
+            }
+            FTerm::App(func, arg) => match self.infer(func)? {
+                Type::Arrow(param, result) => {
+                    let actual = self.infer(arg)?;
+                    if alpha_equivalent(&param, &actual) {
+                        Ok(*result)
+                    } else {
+                        Err(format!(
+                            "Expected `{}` to have type `{}`, found `{}`",
+                            arg, param, actual
+                        ))
+                    }
+                }
+                ty => Err(format!(
+                    "Expected `{}` to be a function, found `{}`",
+                    func, ty
+                )),
+            },
+            FTerm::TypeLambda(name, body) if self.type_vars.contains(name) => {
+                let mut used: HashSet<char> = self.type_vars.iter().copied().collect();
+                type_names(body, &mut used);
+                let fresh = fresh(&used)?;
+                let body = rename(body, *name, fresh);
+                self.infer(&FTerm::TypeLambda(fresh, Box::new(body)))
+            }
+            FTerm::TypeLambda(name, body) => {
+                self.type_vars.push(*name);
+                let body = self.infer(body);
+                self.type_vars.pop();
+                Ok(types::forall(*name, body?))
+            }
+            FTerm::TypeApp(func, ty) => {
+                self.well_formed(ty)?;
+                match self.infer(func)? {
+                    Type::Forall(name, body) => substitute(&body, name, ty),
+                    actual => Err(format!(
+                        "Expected `{}` to be a type abstraction, found `{}`",
+                        func, actual
+                    )),
+                }
+            }
+        }
+    }
+
+    fn well_formed(&self, ty: &Type) -> Result<(), String> {
+        let mut vars = HashSet::new();
+        free_type_vars(ty, &mut vars);
+        let mut vars: Vec<char> = vars
+            .into_iter()
+            .filter(|var| !self.type_vars.contains(var))
+            .collect();
+        vars.sort_unstable();
+        match vars.first() {
+            Some(var) => Err(format!("Unbound type variable `{}`", var)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn free_type_vars(ty: &Type, vars: &mut HashSet<char>) {
+    match ty {
+        Type::Var(name) => {
+            vars.insert(*name);
+        }
+        Type::Arrow(param, result) => {
+            free_type_vars(param, vars);
+            free_type_vars(result, vars);
+        }
+        Type::Forall(name, body) => {
+            let mut inner = HashSet::new();
+            free_type_vars(body, &mut inner);
+            inner.remove(name);
+            vars.extend(inner);
+        }
+    }
+}
+
+/// Capture-avoiding substitution of `replacement` for `name` in `ty`.
+fn substitute(ty: &Type, name: char, replacement: &Type) -> Result<Type, String> {
+    match ty {
+        Type::Var(var) if *var == name => Ok(replacement.clone()),
+        Type::Var(_) => Ok(ty.clone()),
+        Type::Arrow(param, result) => Ok(types::arrow(
+            substitute(param, name, replacement)?,
+            substitute(result, name, replacement)?,
+        )),
+        Type::Forall(var, _) if *var == name => Ok(ty.clone()),
+        Type::Forall(var, body) => {
+            let mut free = HashSet::new();
+            free_type_vars(replacement, &mut free);
+            if !free.contains(var) {
+                return Ok(types::forall(*var, substitute(body, name, replacement)?));
+            }
+            free_type_vars(body, &mut free);
+            free.insert(name);
+            let fresh = fresh(&free)?;
+            let body = substitute(body, *var, &types::var(fresh))?;
+            Ok(types::forall(fresh, substitute(&body, name, replacement)?))
+        }
+    }
+}
+
+fn fresh(used: &HashSet<char>) -> Result<char, String> {
+    ('a'..='z')
+        .find(|letter| !used.contains(letter))
+        .ok_or_else(|| String::from("Ran out of type variable names"))
+}
+
+/// Every type variable named in the annotations of `term`, bound or free.
+fn type_names(term: &FTerm, names: &mut HashSet<char>) {
+    fn walk(ty: &Type, names: &mut HashSet<char>) {
+        match ty {
+            Type::Var(name) => {
+                names.insert(*name);
+            }
+            Type::Arrow(param, result) => {
+                walk(param, names);
+                walk(result, names);
+            }
+            Type::Forall(name, body) => {
+                names.insert(*name);
+                walk(body, names);
+            }
+        }
+    }
+
+    match term {
+        FTerm::Var(_) => (),
+        FTerm::Lambda(_, ty, body) => {
+            walk(ty, names);
+            type_names(body, names);
+        }
+        FTerm::App(func, arg) => {
+            type_names(func, names);
+            type_names(arg, names);
+        }
+        FTerm::TypeLambda(name, body) => {
+            names.insert(*name);
+            type_names(body, names);
+        }
+        FTerm::TypeApp(func, ty) => {
+            type_names(func, names);
+            walk(ty, names);
+        }
+    }
+}
+
+/// Renames the type variable `from` to the unused `to` in `term`.
+fn rename(term: &FTerm, from: char, to: char) -> FTerm {
+    fn rename_type(ty: &Type, from: char, to: char) -> Type {
+        match ty {
+            Type::Var(name) if *name == from => types::var(to),
+            Type::Var(_) => ty.clone(),
+            Type::Arrow(param, result) => {
+                types::arrow(rename_type(param, from, to), rename_type(result, from, to))
+            }
+            Type::Forall(name, _) if *name == from => ty.clone(),
+            Type::Forall(name, body) => types::forall(*name, rename_type(body, from, to)),
+        }
+    }
+
+    match term {
+        FTerm::Var(_) => term.clone(),
+        FTerm::Lambda(name, ty, body) => FTerm::Lambda(
+            *name,
+            rename_type(ty, from, to),
+            Box::new(rename(body, from, to)),
+        ),
+        FTerm::App(func, arg) => FTerm::App(
+            Box::new(rename(func, from, to)),
+            Box::new(rename(arg, from, to)),
+        ),
+        FTerm::TypeLambda(name, _) if *name == from => term.clone(),
+        FTerm::TypeLambda(name, body) => FTerm::TypeLambda(*name, Box::new(rename(body, from, to))),
+        FTerm::TypeApp(func, ty) => {
+            FTerm::TypeApp(Box::new(rename(func, from, to)), rename_type(ty, from, to))
+        }
+    }
+}
+
+fn alpha_equivalent(ty1: &Type, ty2: &Type) -> bool {
+    fn equivalent(ty1: &Type, ty2: &Type, bound: &mut Vec<(char, char)>) -> bool {
+        match (ty1, ty2) {
+            (Type::Var(name1), Type::Var(name2)) => {
+                match bound
+                    .iter()
+                    .rev()
+                    .find(|(var1, var2)| var1 == name1 || var2 == name2)
+                {
+                    Some((var1, var2)) => var1 == name1 && var2 == name2,
+                    None => name1 == name2,
+                }
+            }
+            (Type::Arrow(param1, result1), Type::Arrow(param2, result2)) => {
+                equivalent(param1, param2, bound) && equivalent(result1, result2, bound)
+            }
+            (Type::Forall(name1, body1), Type::Forall(name2, body2)) => {
+                bound.push((*name1, *name2));
+                let result = equivalent(body1, body2, bound);
+                bound.pop();
+                result
+            }
+            _ => false,
+        }
+    }
+
+    equivalent(ty1, ty2, &mut Vec::new())
+}
+
+/// Erases every type, leaving an untyped term.
+pub fn erase(term: &FTerm) -> Term {
+    match term {
+        FTerm::Var(name) => construct::var(*name),
+        FTerm::Lambda(name, _, body) => construct::lambda(*name, erase(body)),
+        FTerm::App(func, arg) => construct::app(erase(func), erase(arg)),
+        FTerm::TypeLambda(_, body) | FTerm::TypeApp(body, _) => erase(body),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline;
+
+    macro_rules! ok {
+        ($text:expr, $expected:expr, $ty:expr) => {
+            match pipeline::system_f(&$text) {
+                Ok((term, ty)) => {
+                    assert_eq!(format!("{}", term), $expected);
+                    assert_eq!(format!("{}", ty), $ty);
+                }
+                Err(err) => panic!("{}", err),
+            }
+        };
+    }
+
+    macro_rules! err {
+        ($text:literal, $expected:literal) => {
+            if let Err(err) = pipeline::system_f($text) {
+                assert_eq!(err, $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    #[test]
+    fn test() {
+        ok!("([a] -> (x [a] -> x))", "(x -> x)", "[a] -> a -> a");
+        ok!(
+            "((([a] -> (x [a] -> x)) [[b] -> b -> b]) ([b] -> (y [b] -> y)))",
+            "(y -> y)",
+            "[b] -> b -> b"
+        );
+        ok!(
+            "([a] -> ([b] -> (f [[c] -> c -> a] -> (y [b] -> ((f [b]) y)))))",
+            "(f -> (y -> (f y)))",
+            "[a] -> [b] -> ([c] -> c -> a) -> b -> a"
+        );
+        ok!(
+            "([b] -> (k [[a] -> [b] -> a -> b -> a] -> (k [b])))",
+            "(k -> k)",
+            "[b] -> ([a] -> [b] -> a -> b -> a) -> [c] -> b -> c -> b"
+        );
+        err!("(x -> x)", "Expected a type annotation on `x`");
+        err!("(x [a] -> x)", "Unbound type variable `a`");
+        err!("([a] -> (x [a] -> y))", "Unbound variable `y`");
+        err!(
+            "([a] -> (x [a] -> (x x)))",
+            "Expected `x` to be a function, found `a`"
+        );
+        err!(
+            "([a] -> (x [a] -> ((y [a -> a] -> y) x)))",
+            "Expected `x` to have type `a -> a`, found `a`"
+        );
+        err!(
+            "([a] -> (x [a] -> (x [a])))",
+            "Expected `x` to be a type abstraction, found `a`"
+        );
+        ok!(
+            "([a] -> ([a] -> (x [a] -> x)))",
+            "(x -> x)",
+            "[a] -> [b] -> b -> b"
+        );
+        ok!(
+            "([a] -> (x [a] -> ([a] -> (y [a] -> x))))",
+            "(x -> (y -> x))",
+            "[a] -> a -> [b] -> b -> a"
+        );
+        err!("(f : f)", "Definitions are not supported in System F");
+
+        let letters: String = ('a'..='z').map(|name| format!("([{}] -> ", name)).collect();
+        let text = format!("{}([a] -> (x [a] -> x)){}", letters, ")".repeat(26));
+        assert_eq!(
+            pipeline::system_f(&text),
+            Err(String::from("Ran out of type variable names"))
+        );
+    }
+
+    #[test]
+    fn prelude() {
+        for (name, source, ty) in PRELUDE {
+            match pipeline::system_f(source) {
+                Ok((_, actual)) => assert_eq!(format!("{}", actual), *ty, "{}", name),
+                Err(err) => panic!("{}: {}", name, err),
+            }
+        }
+
+        let get = |name| super::prelude(name).unwrap();
+        let nat = "[a] -> (a -> a) -> a -> a";
+        let bool = "[a] -> a -> a -> a";
+        ok!(get("two"), "(f -> (x -> (f (f x))))", nat);
+        ok!(
+            format!("({} {})", get("succ"), get("one")),
+            "(f -> (x -> (f (f x))))",
+            nat
+        );
+        ok!(
+            format!("(({} {}) {})", get("plus"), get("one"), get("two")),
+            "(f -> (x -> (f (f (f x)))))",
+            nat
+        );
+        ok!(
+            format!("({} {})", get("iszero"), get("zero")),
+            "(x -> (y -> x))",
+            bool
+        );
+        ok!(
+            format!("({} {})", get("iszero"), get("one")),
+            "(x -> (y -> y))",
+            bool
+        );
+        ok!(
+            format!("(({} {}) {})", get("and"), get("true"), get("false")),
+            "(x -> (y -> y))",
+            bool
+        );
+        ok!(
+            format!("({} {})", get("not"), get("false")),
+            "(x -> (y -> x))",
+            bool
+        );
+        ok!(
+            format!(
+                "((({} [{}]) [{}]) (((({} [{}]) [{}]) {}) {}))",
+                get("fst"),
+                nat,
+                bool,
+                get("pair"),
+                nat,
+                bool,
+                get("two"),
+                get("true")
+            ),
+            "(f -> (x -> (f (f x))))",
+            nat
+        );
+    }
+
+    #[test]
+    fn output() {
+        let term = FTerm::TypeLambda(
+            'a',
+            Box::new(FTerm::Lambda(
+                'x',
+                types::var('a'),
+                Box::new(FTerm::TypeApp(
+                    Box::new(FTerm::Var('x')),
+                    types::forall('b', types::var('b')),
+                )),
+            )),
+        );
+        assert_eq!(format!("{}", term), "([a] -> (x [a] -> (x [[b] -> b])))");
+        assert_eq!(
+            format!(
+                "{}",
+                types::arrow(types::forall('a', types::var('a')), types::var('b'))
+            ),
+            "([a] -> a) -> b"
+        );
+    }
+}
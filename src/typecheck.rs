@@ -0,0 +1,210 @@
+use crate::construct::Term;
+use crate::define;
+use crate::types;
+use crate::types::Type;
+use std::collections::HashMap;
+
+/// A type error and the preorder index of the term node it was found at.
+pub type TypeError = (usize, String);
+
+/// Checks a simply typed term. Unannotated terms are not checked.
+pub fn typecheck(term: &Term) -> Result<Option<Type>, TypeError> {
+    if !annotated(term) {
+        return Ok(None);
+    }
+    let mut checker = Checker {
+        index: 0,
+        context: HashMap::new(),
+    };
+    Ok(Some(checker.infer(term)?))
+}
+
+/// The id of the variable or binder at `index`.
+pub fn defined(index: usize, id: Option<usize>) -> Result<usize, TypeError> {
+    define::id(id).map_err(|err| (index, err))
+}
+
+fn annotated(term: &Term) -> bool {
+    match term {
+        Term::Var(..) => false,
+        Term::Lambda(_, _, ty, body) => ty.is_some() || annotated(body),
+        Term::App(func, arg) | Term::Let(_, _, _, func, arg) => annotated(func) || annotated(arg),
+        Term::Def(_, _, body) => annotated(body),
+    }
+}
+
+struct Checker {
+    index: usize,
+    context: HashMap<usize, Type>,
+}
+
+impl Checker {
+    fn visit(&mut self) -> usize {
+        self.index += 1;
+        self.index - 1
+    }
+
+    fn infer(&mut self, term: &Term) -> Result<Type, TypeError> {
+        let index = self.visit();
+        match term {
+            Term::Var(name, id) => match self.context.get(&defined(index, *id)?) {
+                Some(ty) => Ok(ty.clone()),
+                None => Err((
+                    index,
+                    format!("Cannot infer the type of free variable `{}`", name),
+                )),
+            },
+            Term::Lambda(name, id, ty, body) => match ty {
+                Some(ty) => {
+                    let id = defined(index, *id)?;
+                    self.context.insert(id, ty.clone());
+                    let result = self.infer(body)?;
+                    self.context.remove(&id);
+                    Ok(types::arrow(ty.clone(), result))
+                }
+                None => Err((
+                    index,
+                    format!("Cannot infer the type of `{}` without an annotation", name),
+                )),
+            },
+            Term::App(func, arg) => {
+                let func_index = self.index;
+                match self.infer(func)? {
+                    Type::Arrow(param, result) => {
+                        self.check(arg, &param)?;
+                        Ok(*result)
+                    }
+                    ty => Err((func_index, format!("Expected a function, found `{}`", ty))),
+                }
+            }
+            Term::Def(_, _, body) => self.infer(body),
+            Term::Let(_, id, _, value, body) => {
+                let id = defined(index, *id)?;
+                let ty = self.infer(value)?;
+                self.context.insert(id, ty);
+                let result = self.infer(body)?;
+                self.context.remove(&id);
+                Ok(result)
+            }
+        }
+    }
+
+    fn check(&mut self, term: &Term, expected: &Type) -> Result<(), TypeError> {
+        if let Term::Lambda(name, id, ty, body) = term {
+            let index = self.visit();
+            let (param, result) = match expected {
+                Type::Arrow(param, result) => (param, result),
+                Type::Var(_) | Type::Forall(..) => {
+                    return Err((
+                        index,
+                        format!("Expected type `{}`, found a function", expected),
+                    ))
+                }
+            };
+            if let Some(ty) = ty {
+                if ty != &**param {
+                    return Err((
+                        index,
+                        format!(
+                            "Expected `{}` to have type `{}`, found `{}`",
+                            name, param, ty
+                        ),
+                    ));
+                }
+            }
+            let id = defined(index, *id)?;
+            self.context.insert(id, (**param).clone());
+            self.check(body, result)?;
+            self.context.remove(&id);
+            return Ok(());
+        }
+
+        let index = self.index;
+        let actual = self.infer(term)?;
+        if actual == *expected {
+            Ok(())
+        } else {
+            Err((
+                index,
+                format!("Expected type `{}`, found `{}`", expected, actual),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipeline;
+    use crate::pipeline::Pipeline;
+    use crate::pipeline::PipelineResult;
+
+    macro_rules! ok {
+        ($text:literal, $expected:literal) => {
+            if let Ok(PipelineResult::Term(term)) = pipeline::pipeline(Pipeline::Define, $text) {
+                let ty = typecheck(&term).unwrap().unwrap();
+                assert_eq!(format!("{}", ty), $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    macro_rules! err {
+        ($text:literal, $expected:literal) => {
+            if let Err(err) = pipeline::pipeline(Pipeline::Typecheck, $text) {
+                assert_eq!(err, $expected);
+            } else {
+                panic!();
+            }
+        };
+    }
+
+    #[test]
+    fn test() {
+        ok!("(x [a] -> x)", "a -> a");
+        ok!("(x [a] -> (y [b] -> x))", "a -> b -> a");
+        ok!("(f [a -> b] -> (x [a] -> (f x)))", "(a -> b) -> a -> b");
+        ok!("((x [a -> a] -> x) (y -> y))", "a -> a");
+        ok!(
+            "((f [(a -> a) -> b -> b] -> f) (g -> (y -> y)))",
+            "(a -> a) -> b -> b"
+        );
+        ok!("(x [a] -> ((y [a] -> y) x))", "a -> a");
+        err!("(x [a] -> (x x))", "1:12: Expected a function, found `a`");
+        err!(
+            "(x [a] -> y)",
+            "1:11: Cannot infer the type of free variable `y`"
+        );
+        err!(
+            "(x [a] -> ((y [b] -> y) x))",
+            "1:25: Expected type `b`, found `a`"
+        );
+        err!(
+            "((x [a] -> x) (y -> y))",
+            "1:15: Expected type `a`, found a function"
+        );
+        err!(
+            "((x [a -> a] -> x) (y [b] -> y))",
+            "1:20: Expected `y` to have type `a`, found `b`"
+        );
+        err!(
+            "(f -> (x [a] -> x))",
+            "1:1: Cannot infer the type of `f` without an annotation"
+        );
+        err!(
+            "(x [a] -> ((y [a] -> y)\n  (z [a] -> z)))",
+            "2:3: Expected type `a`, found a function"
+        );
+    }
+
+    #[test]
+    fn untyped() {
+        if let Ok(PipelineResult::Term(term)) = pipeline::pipeline(Pipeline::Define, "(x -> (x x))")
+        {
+            assert_eq!(typecheck(&term), Ok(None));
+        } else {
+            panic!();
+        }
+    }
+}
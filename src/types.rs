@@ -0,0 +1,18 @@
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Var(char),
+    Arrow(Box<Type>, Box<Type>),
+    Forall(char, Box<Type>),
+}
+
+pub fn var(name: char) -> Type {
+    Type::Var(name)
+}
+
+pub fn arrow(param: Type, result: Type) -> Type {
+    Type::Arrow(Box::new(param), Box::new(result))
+}
+
+pub fn forall(name: char, body: Type) -> Type {
+    Type::Forall(name, Box::new(body))
+}